@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_with::{KeyValueMap, serde_as};
+
+/// Description of an MCU: its GPIOs and peripherals.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Chip {
+    #[serde(rename = "$key$")]
+    pub name: String,
+    pub description: Option<String>,
+    /// How the chip's pins are named.
+    ///
+    /// When not given, all known naming schemes are accepted.
+    pub pin_naming: Option<PinNaming>,
+    #[serde(default)]
+    pub gpio_ports: Vec<GpioPort>,
+    #[serde_as(as = "KeyValueMap<_>")]
+    #[serde(default)]
+    pub peripherals: Vec<ChipPeripheral>,
+}
+
+impl Chip {
+    /// Returns whether the chip has the given GPIO.
+    #[must_use]
+    pub fn has_gpio(&self, port: u8, pin: u8) -> bool {
+        self.gpio_ports
+            .iter()
+            .any(|p| p.port == port && pin < p.pins && !p.unavailable.contains(&pin))
+    }
+
+    #[must_use]
+    pub fn peripheral(&self, name: &str) -> Option<&ChipPeripheral> {
+        self.peripherals.iter().find(|p| p.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct GpioPort {
+    pub port: u8,
+    /// Number of pins, numbered from 0.
+    pub pins: u8,
+    /// Pins within `0..pins` that are not available (e.g., not bonded out).
+    #[serde(default)]
+    pub unavailable: Vec<u8>,
+}
+
+/// Pin naming scheme of a chip family.
+///
+/// Each scheme maps a pin name to a (port, pin) tuple and back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PinNaming {
+    /// `P<port>_<pin>`, e.g., `P0_13` (nRF).
+    PortUnderscore,
+    /// `P<port>.<pin>`, e.g., `P0.13`.
+    PortDot,
+    /// `P<port letter><pin>`, e.g., `PA8` or `PB12`, where port `A` is port 0 (STM32).
+    PortLetter,
+    /// `PIN_<pin>`, e.g., `PIN_25`, all on port 0 (RP2040).
+    Pin,
+    /// `GPIO<pin>` or `GPIO_NUM_<pin>`, e.g., `GPIO5`, all on port 0 (ESP32).
+    Gpio,
+    /// `PIO<port>_<pin>`, e.g., `PIO0_12` (LPC).
+    Pio,
+}
+
+/// A peripheral instance, e.g., `UARTE0` or `USART1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct ChipPeripheral {
+    #[serde(rename = "$key$")]
+    pub name: String,
+    pub kind: PeripheralKind,
+    /// Maps a signal name (e.g., `tx`) to the pins it can be routed to.
+    ///
+    /// If this is empty, all signals can be routed to any pin (e.g., on nRF devices). Otherwise,
+    /// signals that are not listed cannot be routed at all.
+    ///
+    /// Signal names are `tx`, `rx`, `cts` and `rts` for UARTs, `sda` and `scl` for I2C and `sck`,
+    /// `mosi` and `miso` for SPI.
+    #[serde(default)]
+    pub signals: BTreeMap<String, Vec<PinRoute>>,
+}
+
+impl ChipPeripheral {
+    /// Returns the route of `signal` to `pin`, if the signal can be routed to that pin.
+    ///
+    /// For peripherals with unrestricted routing, a route without alternate function is returned.
+    #[must_use]
+    pub fn route(&self, signal: &str, pin: &str) -> Option<PinRoute> {
+        if self.signals.is_empty() {
+            return Some(PinRoute {
+                pin: pin.to_string(),
+                af: None,
+            });
+        }
+
+        self.signals
+            .get(signal)?
+            .iter()
+            .find(|route| route.pin == pin)
+            .cloned()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PeripheralKind {
+    Uart,
+    I2c,
+    Spi,
+    Other,
+}
+
+impl PeripheralKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PeripheralKind::Uart => "uart",
+            PeripheralKind::I2c => "i2c",
+            PeripheralKind::Spi => "spi",
+            PeripheralKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct PinRoute {
+    pub pin: String,
+    /// Alternate function number selecting this route, if the MCU uses those.
+    pub af: Option<u8>,
+}
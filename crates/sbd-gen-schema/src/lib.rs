@@ -1,8 +1,10 @@
 pub mod ariel;
+pub mod chip;
 pub mod common;
 pub mod riot;
+pub mod zephyr;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
@@ -10,8 +12,10 @@ use serde_with::{KeyValueMap, serde_as};
 
 use crate::{
     ariel::{Ariel, ArielTargetExt},
-    common::StringOrVecString,
+    chip::Chip,
+    common::{Scalar, StringOrVecString},
     riot::{Riot, RiotTargetExt},
+    zephyr::Zephyr,
 };
 
 const fn default_version() -> Version {
@@ -26,32 +30,141 @@ const fn default_version() -> Version {
 /// In both cases, the schema version must be updated accordingly.
 #[must_use]
 pub const fn schema_version() -> Version {
-    semver::Version::new(0, 4, 0)
+    semver::Version::new(0, 4, 8)
+}
+
+/// JSON pointer to the schema of a single target within the schema of SBD files.
+#[cfg(feature = "schemars")]
+const TARGET_SCHEMA_POINTER: &str = "/$defs/KeyValueMap(Target)/additionalProperties";
+
+/// Returns a JSON Schema describing SBD files of the current schema version.
+///
+/// The schema version is recorded in the schema's `title`.
+///
+/// # Panics
+///
+/// Panics if the generated schema does not have the expected layout, which would be a bug.
+#[cfg(feature = "schemars")]
+#[must_use]
+pub fn json_schema() -> schemars::Schema {
+    let version = schema_version();
+    let mut schema = schemars::schema_for!(SbdFile);
+    schema.insert(
+        "title".into(),
+        format!("SBD file (schema version {version})").into(),
+    );
+
+    // Targets may inherit fields from other targets (`extends`), so even fields that are required
+    // after merging may be missing in a single file.
+    schema
+        .pointer_mut(TARGET_SCHEMA_POINTER)
+        .and_then(|target| target.as_object_mut())
+        .expect("the SBD file schema defines targets")
+        .remove("required");
+
+    schema
+}
+
+/// Returns a JSON Schema describing target fragment files (see [`Target::include`]) of the
+/// current schema version.
+///
+/// A fragment contains optional target fields, except for the target's `chip`, and may carry a
+/// `version`.
+///
+/// # Panics
+///
+/// Panics if the generated schema does not have the expected layout, which would be a bug.
+#[cfg(feature = "schemars")]
+#[must_use]
+pub fn json_schema_fragment() -> schemars::Schema {
+    let version = schema_version();
+    let schema = json_schema();
+
+    let mut fragment = schema
+        .pointer(TARGET_SCHEMA_POINTER)
+        .and_then(|target| target.as_object())
+        .expect("the SBD file schema defines targets")
+        .clone();
+    let file_version = schema
+        .pointer("/properties/version")
+        .expect("the SBD file schema defines `version`")
+        .clone();
+    let properties = fragment
+        .get_mut("properties")
+        .and_then(|properties| properties.as_object_mut())
+        .expect("the target schema has properties");
+    properties.remove("chip");
+    properties.insert("version".into(), file_version);
+
+    for key in ["$schema", "$defs"] {
+        if let Some(value) = schema.get(key) {
+            fragment.insert(key.into(), value.clone());
+        }
+    }
+    fragment.insert(
+        "title".into(),
+        format!("SBD target fragment (schema version {version})").into(),
+    );
+
+    fragment.into()
 }
 
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct SbdFile {
     #[serde(default = "default_version")]
     pub version: Version,
+    /// Other SBD files to merge before this file, relative to this file.
     pub include: Option<Vec<String>>,
     #[serde_as(as = "Option<KeyValueMap<_>>")]
     pub targets: Option<Vec<Target>>,
     pub ariel: Option<Ariel>,
     pub riot: Option<Riot>,
+    pub zephyr: Option<Zephyr>,
     pub description: Option<String>,
+    /// Per-chip information: available GPIOs, peripherals and their pin routing.
+    #[serde_as(as = "Option<KeyValueMap<_>>")]
+    #[serde(default)]
+    pub chips: Option<Vec<Chip>>,
+}
+
+impl SbdFile {
+    /// Returns the description of the chip named `name`, if there is one.
+    #[must_use]
+    pub fn chip(&self, name: &str) -> Option<&Chip> {
+        self.chips.iter().flatten().find(|chip| chip.name == name)
+    }
 }
 
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Target {
     #[serde(rename = "$key$")]
     pub name: String,
     pub chip: String,
     pub description: Option<String>,
+    /// Fragment files containing target fields to merge into this target, relative to the file
+    /// defining the target.
+    ///
+    /// Fragments are merged in the given order, then the target on top: lists are concatenated in
+    /// that order, ending with the target's own entries, and otherwise later values take
+    /// precedence.
     pub include: Option<Vec<String>>,
+    /// Name of a target this target is derived from.
+    ///
+    /// All values of the base target are inherited: maps are merged, lists are appended to and
+    /// other values are overridden by this target's values.
+    pub extends: Option<String>,
+    /// Names of list fields (e.g. `uarts`) that replace the base target's values instead of being
+    /// appended to them.
+    ///
+    /// Only meaningful together with [`Target::extends`].
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub replace: BTreeSet<String>,
     #[serde(default)]
     pub flags: BTreeSet<String>,
     #[serde(default)]
@@ -69,6 +182,15 @@ pub struct Target {
     pub buttons: Vec<Button>,
     #[serde(default)]
     pub uarts: Vec<Uart>,
+    #[serde(default)]
+    pub i2cs: Vec<I2c>,
+    #[serde(default)]
+    pub spis: Vec<Spi>,
+
+    // on-board devices
+    #[serde_as(as = "KeyValueMap<_>")]
+    #[serde(default)]
+    pub devices: Vec<Device>,
 }
 
 impl Target {
@@ -82,21 +204,69 @@ impl Target {
         !self.buttons.is_empty()
     }
 
-    /// Returns true if there are any UARTs listed for this board.
+    /// Returns true if there are any UARTs listed for this board, including the debugger's UART.
     #[must_use]
     pub fn has_uarts(&self) -> bool {
-        !self.uarts.is_empty()
+        !self.uarts.is_empty() || self.debugger_uart().is_some()
     }
 
     /// Returns true if there are any UARTs listed for this board that have the
-    /// [`Uart::host_facing`] property.
+    /// [`Uart::host_facing`] property, or if the debugger has a UART.
     #[must_use]
     pub fn has_host_facing_uart(&self) -> bool {
-        self.uarts.iter().any(|u| u.host_facing)
+        self.uarts.iter().any(|u| u.host_facing) || self.debugger_uart().is_some()
+    }
+
+    /// Returns the debugger's UART, if any.
+    #[must_use]
+    pub fn debugger_uart(&self) -> Option<&Uart> {
+        self.debugger.as_ref().and_then(|d| d.uart.as_ref())
+    }
+
+    /// Returns true if there are any I2C buses listed for this board.
+    #[must_use]
+    pub fn has_i2cs(&self) -> bool {
+        !self.i2cs.is_empty()
+    }
+
+    /// Returns true if there are any SPI buses listed for this board.
+    #[must_use]
+    pub fn has_spis(&self) -> bool {
+        !self.spis.is_empty()
+    }
+
+    /// Returns true if there are any on-board devices listed for this board.
+    #[must_use]
+    pub fn has_devices(&self) -> bool {
+        !self.devices.is_empty()
     }
+
+    /// Looks up a bus by one of its aliases or its implicit name (e.g., `i2c0` or `spi1`).
+    #[must_use]
+    pub fn find_bus(&self, name: &str) -> Option<BusRef<'_>> {
+        let i2c = self.i2cs.iter().enumerate().find(|(n, i2c)| {
+            i2c.aliases.iter().any(|alias| alias == name) || format!("i2c{n}") == name
+        });
+        if let Some((n, i2c)) = i2c {
+            return Some(BusRef::I2c(n, i2c));
+        }
+
+        let spi = self.spis.iter().enumerate().find(|(n, spi)| {
+            spi.aliases.iter().any(|alias| alias == name) || format!("spi{n}") == name
+        });
+        spi.map(|(n, spi)| BusRef::Spi(n, spi))
+    }
+}
+
+/// A bus as returned by [`Target::find_bus()`], along with its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusRef<'a> {
+    I2c(usize, &'a I2c),
+    Spi(usize, &'a Spi),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Led {
     pub pin: String,
@@ -107,15 +277,47 @@ pub struct Led {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Button {
     pub pin: String,
     pub active: Option<PinActive>,
     #[serde(default)]
     pub aliases: Vec<String>,
+    /// Input pull configuration.
+    ///
+    /// If not set, this is derived from [`Button::active`], see [`Button::effective_pull()`].
+    pub pull: Option<Pull>,
+    /// Suggested debounce time in milliseconds.
+    pub debounce_ms: Option<u32>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+impl Button {
+    /// Returns the pull configuration the input needs.
+    ///
+    /// Unless set explicitly, buttons that are active low get a pull-up, and buttons that are
+    /// active high get a pull-down.
+    #[must_use]
+    pub fn effective_pull(&self) -> Pull {
+        self.pull.unwrap_or(match self.active {
+            Some(PinActive::Low) => Pull::Up,
+            Some(PinActive::High) | None => Pull::Down,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub enum Pull {
+    Up,
+    Down,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub enum PinActive {
     #[serde(rename = "high")]
@@ -125,6 +327,7 @@ pub enum PinActive {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
@@ -133,6 +336,7 @@ pub enum Quirk {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct SetPinOp {
     pub description: Option<String>,
@@ -141,6 +345,7 @@ pub struct SetPinOp {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "snake_case")]
 pub enum PinLevel {
@@ -150,14 +355,19 @@ pub enum PinLevel {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Debugger {
     #[serde(rename = "type")]
     pub type_: String,
+    /// UART exposed by the debugger (e.g., as USB serial device).
+    ///
+    /// This is always host facing, regardless of [`Uart::host_facing`].
     pub uart: Option<Uart>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Uart {
     #[serde(default)]
@@ -183,16 +393,15 @@ pub struct Uart {
     ///
     /// # Future development
     ///
-    /// When future versions of `sbd` or the OSes consuming this file learn to process per-MCU
-    /// information, this field might go away. Instead, the possible peripherals might be deduced
-    /// purely from the MCU's peripheral mapping and the `*_pin` values.
+    /// When the target's chip is described in [`SbdFile::chips`], `sbd` checks that all listed
+    /// peripherals exist and can be routed to the `*_pin` values. In the future, this field might
+    /// go away; instead, the possible peripherals might be deduced purely from the MCU's
+    /// peripheral mapping and the `*_pin` values.
     ///
-    /// When multiple UARTs are in use in an application and their possible peripherals overlap,
-    /// deciding which of the choices to take is a [hard problem]. When none of the peripherals are
-    /// available, the OS's mechanism of choosing a peripheral may need enhancing: For example,
-    /// Ariel OS (at the time of writing) only selects the first peripheral. Future versions might
-    /// pick the first one that has not previously been taken, and ideally, a static choice would
-    /// be made at build time solving the satisfiability problem.
+    /// When multiple buses are in use and their possible peripherals overlap, deciding which of
+    /// the choices to take is a [hard problem]. `sbd` makes that choice at build time for all of a
+    /// target's buses together, preferring peripherals that are listed first, and passes only the
+    /// chosen peripheral to the OS.
     ///
     /// When no peripheral is given, or all are used for other purposes, the OS may fall back to
     /// bit-banging operation; currently, they do not.
@@ -212,6 +421,127 @@ pub struct Uart {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct I2c {
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub sda_pin: String,
+    pub scl_pin: String,
+    /// Peripheral device names, any of which is fundamentally available to serve this bus as the
+    /// peripheral that takes control of the SDA and SCL pins.
+    ///
+    /// See [`Uart::possible_peripherals`] for details, the same considerations apply.
+    #[serde(default)]
+    pub possible_peripherals: Vec<String>,
+    /// Default bus frequency in Hz.
+    ///
+    /// If not set, the OS default is used (typically 100 kHz).
+    pub frequency: Option<u32>,
+    /// Set if the board has external pull-up resistors on SDA and SCL.
+    ///
+    /// If this is not set, OSes should enable the MCU's internal pull-ups (if available).
+    #[serde(default)]
+    pub external_pull_ups: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Spi {
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub sck_pin: String,
+    pub mosi_pin: Option<String>,
+    pub miso_pin: Option<String>,
+    /// Chip-select lines of the devices attached to this bus.
+    #[serde(default)]
+    pub cs: Vec<SpiChipSelect>,
+    /// Peripheral device names, any of which is fundamentally available to serve this bus as the
+    /// peripheral that takes control of the SCK, MOSI and MISO pins.
+    ///
+    /// See [`Uart::possible_peripherals`] for details, the same considerations apply.
+    #[serde(default)]
+    pub possible_peripherals: Vec<String>,
+    /// Maximum bus frequency in Hz supported by the board's wiring.
+    pub max_frequency: Option<u32>,
+    /// Default SPI mode (clock polarity and phase).
+    pub mode: Option<SpiMode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct SpiChipSelect {
+    pub pin: String,
+    /// Name of the device attached to this chip-select line.
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub enum SpiMode {
+    Mode0,
+    Mode1,
+    Mode2,
+    Mode3,
+}
+
+/// An on-board device, e.g., a sensor or an external flash chip.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Device {
+    #[serde(rename = "$key$")]
+    pub name: String,
+    /// Device model, e.g., `lis3dh` or `mx25r6435f`.
+    ///
+    /// This is expected to match the name of the driver in the OSes consuming this file.
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub class: DeviceClass,
+    pub description: Option<String>,
+    /// Alias or implicit name (e.g. `i2c0`) of the bus this device is attached to.
+    pub bus: Option<String>,
+    /// Bus address, for devices on I2C buses.
+    pub address: Option<u16>,
+    /// Chip-select pin, for devices on SPI buses.
+    pub cs_pin: Option<String>,
+    #[serde(default)]
+    pub int_pins: Vec<String>,
+    pub enable_pin: Option<String>,
+    /// Free-form, device specific properties.
+    #[serde(default)]
+    pub properties: BTreeMap<String, Scalar>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceClass {
+    Sensor,
+    Flash,
+    Radio,
+    Other,
+}
+
+impl DeviceClass {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceClass::Sensor => "sensor",
+            DeviceClass::Flash => "flash",
+            DeviceClass::Radio => "radio",
+            DeviceClass::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SbdFileVersion {
     #[serde(default = "default_version")]
     pub version: Version,
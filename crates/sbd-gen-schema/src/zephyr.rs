@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Zephyr {
+    pub chips: BTreeMap<String, ZephyrChipMapEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ZephyrChipMapEntry {
+    /// Chip name as used in `board.yml`, e.g., `nrf52840`.
+    pub soc: String,
+    /// Kconfig symbol selecting the chip variant, e.g., `SOC_NRF52840_QIAA`.
+    pub soc_kconfig: String,
+    /// Devicetree include of the chip variant, e.g., `nordic/nrf52840_qiaa.dtsi`.
+    pub dtsi: String,
+    /// Node labels of the GPIO controllers, indexed by port, e.g., `[gpio0, gpio1]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gpio_ports: Vec<String>,
+    pub peripherals: Option<ZephyrChipPeripherals>,
+    /// How pins are assigned to peripherals in the devicetree.
+    ///
+    /// Without this, buses cannot be enabled, as their pins cannot be expressed.
+    pub pinctrl: Option<ZephyrPinctrl>,
+}
+
+/// Devicetree pinctrl conventions of a chip family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ZephyrPinctrl {
+    /// Nordic: board-defined pinctrl states selecting pins with `NRF_PSEL()`.
+    Nrf,
+    /// STM32: references to the pin nodes of the HAL (e.g., `&usart1_tx_pa9`).
+    Stm32,
+}
+
+/// Maps peripheral names (e.g., `UARTE0`) to devicetree node labels (e.g., `uart0`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ZephyrChipPeripherals {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub uarts: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub i2cs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub spis: BTreeMap<String, String>,
+}
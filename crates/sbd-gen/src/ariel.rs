@@ -7,17 +7,26 @@
 use std::{collections::HashSet, fmt::Write as _};
 
 use anyhow::{Context as _, Result, anyhow};
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 
 use crate::{
-    filemap::{FileMap, Mode, parse_mode},
+    conflicts::ConflictPolicy,
+    devices::{claim_device_pins, ident_part, resolve_device},
+    filemap::{ApplyOptions, FileMap, Mode, ReportFormat, parse_mode},
+    keep,
     krate::{Crate, DependencyFull},
     laze::{LazeContext, LazeFile},
-    parse_sbd_files,
+    load_sbd_files,
+    manifest::Origin,
     resources::Resources,
 };
 
-use sbd_gen_schema::{PinLevel, Quirk, SbdFile, SetPinOp, Target, common::StringOrVecString};
+use sbd_gen_schema::{
+    BusRef, Device, DeviceClass, PinActive, PinLevel, Pull, Quirk, SbdFile, SetPinOp, SpiMode,
+    Target,
+    chip::Chip,
+    common::{Scalar, StringOrVecString},
+};
 
 #[derive(argh::FromArgs, Debug)]
 #[argh(subcommand, name = "generate-ariel")]
@@ -27,10 +36,26 @@ pub struct GenerateArielArgs {
     #[argh(positional)]
     sbd_dir: String,
 
-    /// operation mode: create|check|update
+    /// operation mode: create|check|update|dry-run
     #[argh(option, short = 'm', from_str_fn(parse_mode))]
     mode: Option<Mode>,
 
+    /// print diffs of the differing files (in check and dry-run mode)
+    #[argh(switch)]
+    diff: bool,
+
+    /// check mode result format: text|json (default: text)
+    #[argh(option, default = "ReportFormat::default()")]
+    format: ReportFormat,
+
+    /// write a junit xml report of the check mode results to this file
+    #[argh(option)]
+    junit: Option<Utf8PathBuf>,
+
+    /// overwrite files that were edited by hand (in update mode)
+    #[argh(switch)]
+    force: bool,
+
     /// ariel os boards crate output folder
     #[argh(
         option,
@@ -40,14 +65,22 @@ pub struct GenerateArielArgs {
     output: Utf8PathBuf,
 }
 
-pub fn generate(args: &GenerateArielArgs) -> Result<()> {
-    let sbd_file = parse_sbd_files(args.sbd_dir.as_str())?;
+pub fn generate(args: &GenerateArielArgs, conflicts: ConflictPolicy) -> Result<()> {
+    let (sbd_file, inputs) = load_sbd_files(args.sbd_dir.as_str(), conflicts)?;
     let mode = args.mode.unwrap_or_default();
 
+    let origin = Origin::new::<GenerateArielArgs>(&args.sbd_dir, &args.output, &inputs)?;
+
     // Render the ariel crate.
-    let krate = render_ariel_board_crate(&sbd_file)?;
+    let krate = render_ariel_board_crate(&sbd_file)?.with_origin(origin);
 
-    mode.apply(&args.output, &krate)?;
+    let options = ApplyOptions {
+        diff: args.diff,
+        format: args.format,
+        junit: args.junit.clone(),
+        force: args.force,
+    };
+    mode.apply(&args.output, &krate, &options)?;
 
     Ok(())
 }
@@ -67,7 +100,7 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
         .collect();
 
     if chips.is_empty() {
-        println!("warning: No chips defined for Ariel OS");
+        eprintln!("warning: No chips defined for Ariel OS");
     }
 
     // filter targets with unknown chips
@@ -79,7 +112,7 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
             if chips.contains(&target.chip) {
                 true
             } else {
-                println!(
+                eprintln!(
                     "warning: skipping target {}, unknown chip {}",
                     target.name, target.chip
                 );
@@ -90,7 +123,7 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
         .collect::<Vec<_>>();
 
     if targets.is_empty() {
-        println!("warning: No targets defined for Ariel OS");
+        eprintln!("warning: No targets defined for Ariel OS");
     }
 
     // crate
@@ -136,11 +169,13 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
             .insert("build.rs".into(), render_build_rs(&targets));
 
         for target in &targets {
-            let target_rs = render_target_rs(target)
+            let target_rs = render_target_rs(target, sbd.chip(&target.chip))
                 .with_context(|| anyhow!("cannot render {}", target.name))?;
-            board_crate
-                .files
-                .insert(format!("src/{}.rs", target.name).into(), target_rs);
+            board_crate.files.insert_owned(
+                format!("src/{}.rs", target.name).into(),
+                target_rs,
+                &target.name,
+            );
         }
 
         let mut lib_rs = String::new();
@@ -164,6 +199,11 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
             if target.has_leds() {
                 target_builder.provides.insert("has_leds".into());
             }
+            for color in target.leds.iter().filter_map(|led| led.color.as_ref()) {
+                target_builder
+                    .provides
+                    .insert(format!("has_led_{}", normalize_color(color)));
+            }
             if target.has_buttons() {
                 target_builder.provides.insert("has_buttons".into());
             }
@@ -172,6 +212,9 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
                     .provides
                     .insert("has_host_facing_uart".into());
             }
+            for device in &target.devices {
+                target_builder.provides.insert(device_capability(device));
+            }
 
             if let Some(swi) = target.ariel.swi {
                 target_builder.provides.insert("has_swi".into());
@@ -185,6 +228,20 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
                     .push(config_swi);
             }
 
+            if let Some(ref debugger) = target.debugger {
+                if let Some(protocol) = probe_rs_protocol(&debugger.type_) {
+                    target_builder.env.insert(
+                        "PROBE_RS_PROTOCOL".into(),
+                        StringOrVecString::String(protocol.into()),
+                    );
+                } else {
+                    eprintln!(
+                        "warning: {}: debugger type `{}` is not supported by probe-rs",
+                        target.name, debugger.type_
+                    );
+                }
+            }
+
             // copy over Ariel's global environment
             target_builder.env.extend(target.ariel.global_env.clone());
 
@@ -193,13 +250,31 @@ pub fn render_ariel_board_crate(sbd: &SbdFile) -> Result<FileMap> {
         laze_file.builders = Some(laze_builders);
 
         let mut laze_file_str = String::from("# yamllint disable-file\n\n");
-        laze_file_str.push_str(&laze_file.to_string().unwrap());
+        laze_file_str.push_str(&laze_file.to_string_with_user_sections()?);
 
         // add to crate
         board_crate.files.insert("laze.yml".into(), laze_file_str);
     }
 
-    Ok(board_crate.render())
+    let mut files = board_crate.render();
+    // Added after rendering, as prettyplease drops comments.
+    if let Some(lib_rs) = files.map.get_mut(Utf8Path::new("src/lib.rs")) {
+        lib_rs.push('\n');
+        lib_rs.push_str(&keep::user_section("//", "modules"));
+    }
+
+    Ok(files)
+}
+
+/// Returns the probe-rs protocol to use with a debugger type, if probe-rs supports the debugger.
+fn probe_rs_protocol(debugger_type: &str) -> Option<&'static str> {
+    match debugger_type {
+        "cmsis-dap" | "daplink" | "jlink" | "j-link" | "stlink" | "st-link" | "probe-rs" => {
+            Some("swd")
+        }
+        "esp-usb-jtag" | "ftdi" => Some("jtag"),
+        _ => None,
+    }
 }
 
 fn render_targets_dispatch(targets: &[Target]) -> String {
@@ -227,8 +302,8 @@ struct RenderTarget<'a> {
 }
 
 impl<'a> RenderTarget<'a> {
-    pub fn new(target: &'a Target) -> Self {
-        let resources = Resources::new(target);
+    pub fn new(target: &'a Target, chip: Option<&Chip>) -> Self {
+        let resources = Resources::new(target, chip, None);
         Self { target, resources }
     }
 
@@ -238,7 +313,12 @@ impl<'a> RenderTarget<'a> {
         pins.push_str("pub mod pins {\n");
         let target = self.target;
 
-        if target.has_leds() || target.has_buttons() || target.has_uarts() {
+        if target.has_leds()
+            || target.has_buttons()
+            || target.has_uarts()
+            || target.has_i2cs()
+            || target.has_spis()
+        {
             if target.has_leds() {
                 pins.push_str(&self.render_led_pins()?);
             }
@@ -248,6 +328,12 @@ impl<'a> RenderTarget<'a> {
             if target.has_uarts() {
                 pins.push_str(&self.render_uarts()?);
             }
+            if target.has_i2cs() {
+                pins.push_str(&self.render_i2cs()?);
+            }
+            if target.has_spis() {
+                pins.push_str(&self.render_spis()?);
+            }
         }
 
         pins.push_str("}\n");
@@ -269,6 +355,24 @@ impl<'a> RenderTarget<'a> {
 
         leds_rs.push_str("});\n");
 
+        // Pass the LEDs' properties on to the OS so that its LED driver can handle active levels
+        // and aliases.
+        leds_rs.push_str("ariel_os_hal::define_leds![\n");
+
+        for (n, led) in leds.iter().enumerate() {
+            let active = match led.active {
+                Some(PinActive::Low) => "Low",
+                Some(PinActive::High) | None => "High",
+            };
+            let _ = write!(leds_rs, "{{ name: led{n}, active: {active}");
+            if let Some(color) = &led.color {
+                let _ = write!(leds_rs, ", color: {}", normalize_color(color));
+            }
+            let _ = writeln!(leds_rs, ", aliases: [{}] }},", led.aliases.join(", "));
+        }
+
+        leds_rs.push_str("];\n");
+
         Ok(leds_rs)
     }
 
@@ -286,17 +390,54 @@ impl<'a> RenderTarget<'a> {
 
         buttons_rs.push_str("});\n");
 
+        // Pass the buttons' properties on to the OS so that it can configure the inputs.
+        buttons_rs.push_str("ariel_os_hal::define_buttons![\n");
+
+        for (n, button) in buttons.iter().enumerate() {
+            let active = match button.active {
+                Some(PinActive::Low) => "Low",
+                Some(PinActive::High) | None => "High",
+            };
+            let pull = match button.effective_pull() {
+                Pull::Up => "Up",
+                Pull::Down => "Down",
+                Pull::None => "None",
+            };
+            let _ = write!(
+                buttons_rs,
+                "{{ name: button{n}, active: {active}, pull: {pull}"
+            );
+            if let Some(debounce_ms) = button.debounce_ms {
+                let _ = write!(buttons_rs, ", debounce_ms: {debounce_ms}");
+            }
+            let _ = writeln!(buttons_rs, ", aliases: [{}] }},", button.aliases.join(", "));
+        }
+
+        buttons_rs.push_str("];\n");
+
         Ok(buttons_rs)
     }
 
     fn render_uarts(&mut self) -> Result<String> {
-        let uarts = &self.target.uarts;
+        let target = self.target;
         let mut code = String::new();
 
         code.push_str("ariel_os_hal::define_uarts![\n");
 
-        for (n, uart) in uarts.iter().enumerate() {
-            let name = format!("uart{n}");
+        // The debugger's UART comes first, and is always host facing.
+        let uarts = target
+            .debugger_uart()
+            .map(|uart| ("debugger_uart".to_string(), uart, true))
+            .into_iter()
+            .chain(
+                target
+                    .uarts
+                    .iter()
+                    .enumerate()
+                    .map(|(n, uart)| (format!("uart{n}"), uart, uart.host_facing)),
+            );
+
+        for (name, uart, host_facing) in uarts {
             {
                 // claim this UART's resources
                 // TODO: "by" could be more specific ("claimed by uart FOO as rx_pin" vs "claimed
@@ -311,32 +452,24 @@ impl<'a> RenderTarget<'a> {
                     self.resources.claim(rts_pin, &name)?;
                 }
 
-                // Note: We claim uart "device" later, after actually figuring out which one to use.
+                // Note: The uart "device" was assigned (and thus claimed) when creating the
+                // resources, considering all buses of the target.
             }
 
-            let Some(device) = uart.possible_peripherals.first() else {
+            let Some(device) = self.resources.peripheral(&name) else {
                 eprintln!(
-                    "warning: No peripheral defined for UART, making it unusable in Ariel output."
+                    "warning: No peripheral available for UART, making it unusable in Ariel output."
                 );
                 eprintln!("Affected UART: {uart:?}");
                 continue;
             };
-            if uart.possible_peripherals.len() > 1 {
-                eprintln!(
-                    "warning: Multiple hardware devices are available, but Ariel OS does not process any but the first."
-                );
-                eprintln!("Affected UART: {uart:?}");
-            }
-
-            // claiming uart "device" here
-            self.resources.claim(device, &name)?;
 
             // Deferring to a macro so that any actual logic in there is handled in the OS where it
             // belongs; this merely processes the data into a format usable there.
             writeln!(
                 code,
                 "{{ name: {}, device: {}, tx: {}, rx: {}, host_facing: {} }},",
-                name, device, uart.tx_pin, uart.rx_pin, uart.host_facing
+                name, device, uart.tx_pin, uart.rx_pin, host_facing
             )
             .unwrap();
         }
@@ -345,17 +478,218 @@ impl<'a> RenderTarget<'a> {
 
         Ok(code)
     }
+
+    fn render_i2cs(&mut self) -> Result<String> {
+        let i2cs = &self.target.i2cs;
+        let mut code = String::new();
+
+        code.push_str("ariel_os_hal::define_i2cs![\n");
+
+        for (n, i2c) in i2cs.iter().enumerate() {
+            let name = format!("i2c{n}");
+
+            // claim this bus' pins, the device was assigned when creating the resources
+            self.resources.claim(&i2c.sda_pin, &name)?;
+            self.resources.claim(&i2c.scl_pin, &name)?;
+
+            let Some(device) = self.resources.peripheral(&name) else {
+                eprintln!(
+                    "warning: No peripheral available for I2C bus, making it unusable in Ariel output."
+                );
+                eprintln!("Affected I2C bus: {i2c:?}");
+                continue;
+            };
+
+            let _ = write!(
+                code,
+                "{{ name: {}, device: {}, sda: {}, scl: {}, internal_pull_ups: {}",
+                name, device, i2c.sda_pin, i2c.scl_pin, !i2c.external_pull_ups
+            );
+            if let Some(frequency) = i2c.frequency {
+                let _ = write!(code, ", frequency: {frequency}");
+            }
+            code.push_str(" },\n");
+        }
+
+        code.push_str("];\n");
+
+        Ok(code)
+    }
+
+    fn render_spis(&mut self) -> Result<String> {
+        let spis = &self.target.spis;
+        let mut code = String::new();
+
+        code.push_str("ariel_os_hal::define_spis![\n");
+
+        for (n, spi) in spis.iter().enumerate() {
+            let name = format!("spi{n}");
+
+            // claim this bus' pins, the device was assigned when creating the resources
+            self.resources.claim(&spi.sck_pin, &name)?;
+            if let Some(ref mosi_pin) = spi.mosi_pin {
+                self.resources.claim(mosi_pin, &name)?;
+            }
+            if let Some(ref miso_pin) = spi.miso_pin {
+                self.resources.claim(miso_pin, &name)?;
+            }
+            for (m, cs) in spi.cs.iter().enumerate() {
+                self.resources.claim(&cs.pin, format!("{name} (cs{m})"))?;
+            }
+
+            let Some(device) = self.resources.peripheral(&name) else {
+                eprintln!(
+                    "warning: No peripheral available for SPI bus, making it unusable in Ariel output."
+                );
+                eprintln!("Affected SPI bus: {spi:?}");
+                continue;
+            };
+
+            let _ = write!(
+                code,
+                "{{ name: {name}, device: {device}, sck: {}",
+                spi.sck_pin
+            );
+            if let Some(ref mosi_pin) = spi.mosi_pin {
+                let _ = write!(code, ", mosi: {mosi_pin}");
+            }
+            if let Some(ref miso_pin) = spi.miso_pin {
+                let _ = write!(code, ", miso: {miso_pin}");
+            }
+            let cs_pins = spi
+                .cs
+                .iter()
+                .map(|cs| cs.pin.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(code, ", cs: [{cs_pins}]");
+            if let Some(mode) = spi.mode {
+                let _ = write!(code, ", mode: {}", ariel_spi_mode(mode));
+            }
+            if let Some(max_frequency) = spi.max_frequency {
+                let _ = write!(code, ", max_frequency: {max_frequency}");
+            }
+            code.push_str(" },\n");
+        }
+
+        code.push_str("];\n");
+
+        Ok(code)
+    }
+
+    pub fn render_devices(&mut self) -> Result<String> {
+        let target = self.target;
+        let mut code = String::new();
+
+        if !target.has_devices() {
+            return Ok(code);
+        }
+
+        code.push_str("pub mod devices {\n");
+
+        for device in &target.devices {
+            let resolved = resolve_device(target, device)?;
+            claim_device_pins(&mut self.resources, &resolved)?;
+
+            let _ = writeln!(code, "pub mod {} {{", resolved.ident());
+            let _ = writeln!(code, "pub const TYPE: &str = \"{}\";", device.type_);
+
+            let mut peripherals = Vec::new();
+            match resolved.bus {
+                Some(BusRef::I2c(n, _)) => {
+                    let _ = writeln!(code, "pub const BUS: &str = \"i2c{n}\";");
+                    if let Some(address) = device.address {
+                        let _ = writeln!(code, "pub const I2C_ADDRESS: u16 = {address:#04x};");
+                    }
+                }
+                Some(BusRef::Spi(n, spi)) => {
+                    let _ = writeln!(code, "pub const BUS: &str = \"spi{n}\";");
+                    // CS pins on the bus' list are handled by the bus, others belong to the device
+                    if let Some(index) = spi
+                        .cs
+                        .iter()
+                        .position(|cs| Some(cs.pin.as_str()) == resolved.cs_pin)
+                    {
+                        let _ = writeln!(code, "pub const SPI_CS_INDEX: usize = {index};");
+                    } else if let Some(cs_pin) = resolved.cs_pin {
+                        peripherals.push(("cs".to_string(), cs_pin));
+                    }
+                }
+                None => {
+                    if let Some(cs_pin) = resolved.cs_pin {
+                        peripherals.push(("cs".to_string(), cs_pin));
+                    }
+                }
+            }
+
+            for (key, value) in &device.properties {
+                let key = ident_part(key).to_ascii_uppercase();
+                let _ = match value {
+                    Scalar::Bool(b) => writeln!(code, "pub const PROPERTY_{key}: bool = {b};"),
+                    Scalar::Integer(i) => writeln!(code, "pub const PROPERTY_{key}: i64 = {i};"),
+                    Scalar::String(s) => {
+                        writeln!(code, "pub const PROPERTY_{key}: &str = {s:?};")
+                    }
+                };
+            }
+
+            for (n, int_pin) in device.int_pins.iter().enumerate() {
+                peripherals.push((format!("int{n}"), int_pin));
+            }
+            if let Some(ref enable_pin) = device.enable_pin {
+                peripherals.push(("enable".to_string(), enable_pin));
+            }
+
+            if !peripherals.is_empty() {
+                code.push_str("ariel_os_hal::define_peripherals!(Peripherals {\n");
+                for (name, pin) in peripherals {
+                    let _ = writeln!(code, "{name}: {pin},");
+                }
+                code.push_str("});\n");
+            }
+
+            code.push_str("}\n");
+        }
+
+        code.push_str("}\n");
+
+        Ok(code)
+    }
 }
 
-fn render_target_rs(target: &Target) -> Result<String> {
-    let mut render_target = RenderTarget::new(target);
+/// Normalizes an LED color name for use in identifiers, e.g., `Light Blue` to `light_blue`.
+fn normalize_color(color: &str) -> String {
+    color.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// Returns the laze capability for a device, e.g., `has_sensor_lis3dh`.
+fn device_capability(device: &Device) -> String {
+    let class = match device.class {
+        DeviceClass::Other => "device",
+        class => class.as_str(),
+    };
+    format!("has_{class}_{}", ident_part(&device.type_))
+}
+
+fn ariel_spi_mode(mode: SpiMode) -> &'static str {
+    match mode {
+        SpiMode::Mode0 => "Mode0",
+        SpiMode::Mode1 => "Mode1",
+        SpiMode::Mode2 => "Mode2",
+        SpiMode::Mode3 => "Mode3",
+    }
+}
+
+fn render_target_rs(target: &Target, chip: Option<&Chip>) -> Result<String> {
+    let mut render_target = RenderTarget::new(target, chip);
     let pins = render_target.render_pins()?;
+    let devices = render_target.render_devices()?;
 
     let mut init_body = String::new();
     handle_quirks(target, &mut init_body);
 
     let target_rs = format!(
-        "// @generated\n\n{pins}\n#[allow(unused_variables)]\npub fn init(peripherals: &mut ariel_os_hal::hal::OptionalPeripherals) {{\n{init_body}}}\n"
+        "// @generated\n\n{pins}\n{devices}#[allow(unused_variables)]\npub fn init(peripherals: &mut ariel_os_hal::hal::OptionalPeripherals) {{\n{init_body}}}\n"
     );
 
     Ok(target_rs)
@@ -431,12 +765,81 @@ pub fn test_default_target() -> Target {
         leds: vec![],
         flags: std::collections::BTreeSet::default(),
         include: None,
+        extends: None,
+        replace: std::collections::BTreeSet::default(),
         uarts: vec![],
+        i2cs: vec![],
+        spis: vec![],
+        devices: vec![],
         quirks: vec![],
         riot: sbd_gen_schema::riot::RiotTargetExt::default(),
     }
 }
 
+#[test]
+fn test_board_crate_user_sections() {
+    let sbd_file = crate::parse_sbd_files("sbd-test-files", ConflictPolicy::Error).unwrap();
+    let files = render_ariel_board_crate(&sbd_file).unwrap();
+    let with_user_content = |file: &str, section: &str, content: &str| {
+        let generated = &files.map[Utf8Path::new(file)];
+        let begin = format!("sbd-gen-user-begin: {section}\n");
+        let existing = generated.replacen(&begin, &format!("{begin}{content}"), 1);
+        assert_ne!(
+            &existing, generated,
+            "{file} has no user section `{section}`"
+        );
+        keep::merge_user_sections(file.into(), &existing, generated).unwrap()
+    };
+
+    // Hand-written additions in the user sections are still valid files.
+    let cargo_toml = with_user_content("Cargo.toml", "dependencies", "extra = \"1.0\"\n");
+    let manifest: toml::Table = toml::from_str(&cargo_toml).unwrap();
+    let dependencies = manifest["dependencies"].as_table().unwrap();
+    assert!(dependencies.contains_key("ariel-os-hal"));
+    assert!(dependencies.contains_key("extra"));
+
+    let laze_yml = with_user_content("laze.yml", "nrf52840dk", "  selects:\n  - extra\n");
+    let laze_file: serde_yaml::Value = serde_yaml::from_str(&laze_yml).unwrap();
+    let builder = &laze_file["builders"][0];
+    assert_eq!(builder["name"].as_str(), Some("nrf52840dk"));
+    assert_eq!(builder["selects"][0].as_str(), Some("extra"));
+
+    let lib_rs = with_user_content("src/lib.rs", "modules", "mod extra;\n");
+    assert!(syn::parse_file(&lib_rs).is_ok());
+}
+
+#[test]
+fn test_manifest_ignores_report_options() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let output = dir.join("ariel-os-boards");
+    let run = |mode, diff, junit: Option<Utf8PathBuf>| {
+        let args = GenerateArielArgs {
+            sbd_dir: "sbd-test-files".to_string(),
+            mode: Some(mode),
+            diff,
+            format: ReportFormat::default(),
+            junit,
+            force: false,
+            output: output.clone(),
+        };
+        generate(&args, ConflictPolicy::Error).unwrap();
+        std::fs::read_to_string(output.join(".sbd-gen")).unwrap()
+    };
+
+    let manifest = run(Mode::Create, false, None);
+    assert!(manifest.contains("command = \"generate-ariel\"\n"));
+
+    // Options that only affect reporting leave the manifest unchanged.
+    assert_eq!(run(Mode::Check, false, None), manifest);
+    assert_eq!(run(Mode::Check, true, None), manifest);
+    assert_eq!(
+        run(Mode::Check, true, Some(dir.join("junit.xml"))),
+        manifest
+    );
+    assert_eq!(run(Mode::Update, true, None), manifest);
+}
+
 #[test]
 fn test_render_uarts() {
     use sbd_gen_schema::Uart;
@@ -466,7 +869,7 @@ fn test_render_uarts() {
         ..test_default_target()
     };
 
-    let mut render_target = RenderTarget::new(&target);
+    let mut render_target = RenderTarget::new(&target, None);
 
     let rendered = render_target.render_uarts().unwrap();
     assert_eq!(
@@ -478,3 +881,283 @@ fn test_render_uarts() {
 "
     );
 }
+
+#[test]
+fn test_render_debugger_uart() {
+    use sbd_gen_schema::{Debugger, Uart};
+    let uart = |rx_pin: &str, tx_pin: &str| Uart {
+        aliases: vec![],
+        rx_pin: rx_pin.to_owned(),
+        tx_pin: tx_pin.to_owned(),
+        cts_pin: None,
+        rts_pin: None,
+        possible_peripherals: vec!["UARTE0".to_owned(), "UARTE1".to_owned()],
+        host_facing: false,
+    };
+
+    let target = Target {
+        debugger: Some(Debugger {
+            type_: "jlink".to_owned(),
+            uart: Some(uart("P0_08", "P0_06")),
+        }),
+        uarts: vec![uart("P1_01", "P1_02")],
+        ..test_default_target()
+    };
+    assert!(target.has_host_facing_uart());
+
+    let mut render_target = RenderTarget::new(&target, None);
+
+    let rendered = render_target.render_uarts().unwrap();
+    assert_eq!(
+        rendered,
+        "ariel_os_hal::define_uarts![
+{ name: debugger_uart, device: UARTE0, tx: P0_06, rx: P0_08, host_facing: true },
+{ name: uart0, device: UARTE1, tx: P1_02, rx: P1_01, host_facing: false },
+];
+"
+    );
+
+    assert_eq!(
+        probe_rs_protocol(&target.debugger.unwrap().type_),
+        Some("swd")
+    );
+}
+
+#[test]
+fn test_render_i2cs() {
+    use sbd_gen_schema::I2c;
+    let i2cs = vec![
+        I2c {
+            aliases: vec![],
+            sda_pin: "P0_26".to_owned(),
+            scl_pin: "P0_27".to_owned(),
+            possible_peripherals: vec!["TWIM0".to_owned()],
+            frequency: None,
+            external_pull_ups: false,
+        },
+        I2c {
+            aliases: vec!["ARDUINO_I2C".to_string()],
+            sda_pin: "P1_02".to_owned(),
+            scl_pin: "P1_03".to_owned(),
+            possible_peripherals: vec!["TWIM1".to_owned()],
+            frequency: Some(400_000),
+            external_pull_ups: true,
+        },
+    ];
+
+    let target = Target {
+        i2cs,
+        ..test_default_target()
+    };
+
+    let mut render_target = RenderTarget::new(&target, None);
+
+    let rendered = render_target.render_i2cs().unwrap();
+    assert_eq!(
+        rendered,
+        "ariel_os_hal::define_i2cs![
+{ name: i2c0, device: TWIM0, sda: P0_26, scl: P0_27, internal_pull_ups: true },
+{ name: i2c1, device: TWIM1, sda: P1_02, scl: P1_03, internal_pull_ups: false, frequency: 400000 },
+];
+"
+    );
+}
+
+#[test]
+fn test_render_spis() {
+    use sbd_gen_schema::{Spi, SpiChipSelect};
+    let spis = vec![
+        Spi {
+            aliases: vec![],
+            sck_pin: "P0_19".to_owned(),
+            mosi_pin: Some("P0_20".to_owned()),
+            miso_pin: Some("P0_21".to_owned()),
+            cs: vec![
+                SpiChipSelect {
+                    pin: "P0_17".to_owned(),
+                    device: Some("mx25r6435f".to_owned()),
+                },
+                SpiChipSelect {
+                    pin: "P0_18".to_owned(),
+                    device: None,
+                },
+            ],
+            possible_peripherals: vec!["SPI3".to_owned()],
+            max_frequency: Some(8_000_000),
+            mode: Some(SpiMode::Mode0),
+        },
+        Spi {
+            aliases: vec![],
+            sck_pin: "P1_15".to_owned(),
+            mosi_pin: Some("P1_13".to_owned()),
+            miso_pin: None,
+            cs: vec![],
+            possible_peripherals: vec!["SPI2".to_owned()],
+            max_frequency: None,
+            mode: None,
+        },
+    ];
+
+    let target = Target {
+        spis,
+        ..test_default_target()
+    };
+
+    let mut render_target = RenderTarget::new(&target, None);
+
+    let rendered = render_target.render_spis().unwrap();
+    assert_eq!(
+        rendered,
+        "ariel_os_hal::define_spis![
+{ name: spi0, device: SPI3, sck: P0_19, mosi: P0_20, miso: P0_21, cs: [P0_17, P0_18], mode: Mode0, max_frequency: 8000000 },
+{ name: spi1, device: SPI2, sck: P1_15, mosi: P1_13, cs: [] },
+];
+"
+    );
+}
+
+#[test]
+fn test_render_devices() {
+    use sbd_gen_schema::I2c;
+    let i2cs = vec![I2c {
+        aliases: vec!["SENSORS".to_string()],
+        sda_pin: "P0_26".to_owned(),
+        scl_pin: "P0_27".to_owned(),
+        possible_peripherals: vec!["TWIM0".to_owned()],
+        frequency: None,
+        external_pull_ups: true,
+    }];
+    let devices = vec![Device {
+        name: "accel".to_owned(),
+        type_: "lis3dh".to_owned(),
+        class: DeviceClass::Sensor,
+        description: None,
+        bus: Some("SENSORS".to_owned()),
+        address: Some(0x19),
+        cs_pin: None,
+        int_pins: vec!["P0_02".to_owned()],
+        enable_pin: None,
+        properties: std::collections::BTreeMap::from([
+            ("rate".to_owned(), Scalar::Integer(100)),
+            ("label.name".to_owned(), Scalar::String("accel".to_owned())),
+            ("2nd-addr".to_owned(), Scalar::Integer(0x18)),
+        ]),
+    }];
+
+    let target = Target {
+        i2cs,
+        devices,
+        ..test_default_target()
+    };
+
+    let mut render_target = RenderTarget::new(&target, None);
+
+    let rendered = render_target.render_devices().unwrap();
+    assert_eq!(
+        rendered,
+        "pub mod devices {
+pub mod accel {
+pub const TYPE: &str = \"lis3dh\";
+pub const BUS: &str = \"i2c0\";
+pub const I2C_ADDRESS: u16 = 0x19;
+pub const PROPERTY_2ND_ADDR: i64 = 24;
+pub const PROPERTY_LABEL_NAME: &str = \"accel\";
+pub const PROPERTY_RATE: i64 = 100;
+ariel_os_hal::define_peripherals!(Peripherals {
+int0: P0_02,
+});
+}
+}
+"
+    );
+    assert!(syn::parse_file(&rendered).is_ok());
+    assert_eq!(device_capability(&target.devices[0]), "has_sensor_lis3dh");
+
+    let device = Device {
+        type_: "lis3dh.v2/spi".to_owned(),
+        ..target.devices[0].clone()
+    };
+    assert_eq!(device_capability(&device), "has_sensor_lis3dh_v2_spi");
+}
+
+#[test]
+fn test_render_led_pins() {
+    use sbd_gen_schema::Led;
+    let leds = vec![
+        Led {
+            pin: "P0_13".to_owned(),
+            color: Some("Red".to_owned()),
+            active: Some(PinActive::Low),
+            aliases: vec!["LED_RED".to_owned(), "LED_USER".to_owned()],
+        },
+        Led {
+            pin: "P0_14".to_owned(),
+            color: None,
+            active: None,
+            aliases: vec![],
+        },
+    ];
+
+    let target = Target {
+        leds,
+        ..test_default_target()
+    };
+
+    let mut render_target = RenderTarget::new(&target, None);
+
+    let rendered = render_target.render_led_pins().unwrap();
+    assert_eq!(
+        rendered,
+        "ariel_os_hal::define_peripherals!(LedPeripherals {
+led0: P0_13,
+led1: P0_14,
+});
+ariel_os_hal::define_leds![
+{ name: led0, active: Low, color: red, aliases: [LED_RED, LED_USER] },
+{ name: led1, active: High, aliases: [] },
+];
+"
+    );
+}
+
+#[test]
+fn test_render_button_pins() {
+    use sbd_gen_schema::Button;
+    let buttons = vec![
+        Button {
+            pin: "P0_11".to_owned(),
+            active: Some(PinActive::Low),
+            aliases: vec!["BTN_USER".to_owned()],
+            pull: None,
+            debounce_ms: Some(20),
+        },
+        Button {
+            pin: "P0_12".to_owned(),
+            active: Some(PinActive::Low),
+            aliases: vec![],
+            pull: Some(Pull::None),
+            debounce_ms: None,
+        },
+    ];
+
+    let target = Target {
+        buttons,
+        ..test_default_target()
+    };
+
+    let mut render_target = RenderTarget::new(&target, None);
+
+    let rendered = render_target.render_button_pins().unwrap();
+    assert_eq!(
+        rendered,
+        "ariel_os_hal::define_peripherals!(ButtonPeripherals {
+button0: P0_11,
+button1: P0_12,
+});
+ariel_os_hal::define_buttons![
+{ name: button0, active: Low, pull: Up, debounce_ms: 20, aliases: [BTN_USER] },
+{ name: button1, active: Low, pull: None, aliases: [] },
+];
+"
+    );
+}
@@ -0,0 +1,255 @@
+//! Checking targets against chip descriptions
+
+use anyhow::{Result, bail};
+
+use sbd_gen_schema::{
+    Quirk, SbdFile, Target,
+    chip::{Chip, PeripheralKind},
+};
+
+use crate::pin_naming::ChipPins;
+
+/// A bus (UART, I2C or SPI) of a target, with the pins it uses.
+#[derive(Debug)]
+pub struct Bus<'a> {
+    /// Implicit name, e.g., `uart0`.
+    pub name: String,
+    pub kind: PeripheralKind,
+    pub possible_peripherals: &'a [String],
+    /// Signal names and the pins they are connected to.
+    pub signals: Vec<(&'static str, &'a str)>,
+}
+
+/// Returns all buses of a target.
+pub fn target_buses(target: &Target) -> Vec<Bus<'_>> {
+    let mut buses = Vec::new();
+
+    let uarts = target
+        .debugger_uart()
+        .map(|uart| ("debugger_uart".to_string(), uart))
+        .into_iter()
+        .chain(
+            target
+                .uarts
+                .iter()
+                .enumerate()
+                .map(|(n, uart)| (format!("uart{n}"), uart)),
+        );
+    for (name, uart) in uarts {
+        let mut signals = vec![("rx", uart.rx_pin.as_str()), ("tx", uart.tx_pin.as_str())];
+        signals.extend(uart.cts_pin.as_deref().map(|pin| ("cts", pin)));
+        signals.extend(uart.rts_pin.as_deref().map(|pin| ("rts", pin)));
+        buses.push(Bus {
+            name,
+            kind: PeripheralKind::Uart,
+            possible_peripherals: &uart.possible_peripherals,
+            signals,
+        });
+    }
+
+    for (n, i2c) in target.i2cs.iter().enumerate() {
+        buses.push(Bus {
+            name: format!("i2c{n}"),
+            kind: PeripheralKind::I2c,
+            possible_peripherals: &i2c.possible_peripherals,
+            signals: vec![("sda", i2c.sda_pin.as_str()), ("scl", i2c.scl_pin.as_str())],
+        });
+    }
+
+    for (n, spi) in target.spis.iter().enumerate() {
+        let mut signals = vec![("sck", spi.sck_pin.as_str())];
+        signals.extend(spi.mosi_pin.as_deref().map(|pin| ("mosi", pin)));
+        signals.extend(spi.miso_pin.as_deref().map(|pin| ("miso", pin)));
+        buses.push(Bus {
+            name: format!("spi{n}"),
+            kind: PeripheralKind::Spi,
+            possible_peripherals: &spi.possible_peripherals,
+            signals,
+        });
+    }
+
+    buses
+}
+
+/// Returns all pins used by a target, along with a description of their use.
+pub fn target_pins(target: &Target) -> Vec<(&str, String)> {
+    let mut pins = Vec::new();
+
+    for (n, led) in target.leds.iter().enumerate() {
+        pins.push((led.pin.as_str(), format!("led{n}")));
+    }
+    for (n, button) in target.buttons.iter().enumerate() {
+        pins.push((button.pin.as_str(), format!("button{n}")));
+    }
+    for bus in target_buses(target) {
+        for (signal, pin) in &bus.signals {
+            pins.push((*pin, format!("{} ({signal})", bus.name)));
+        }
+    }
+    for (n, spi) in target.spis.iter().enumerate() {
+        for (m, cs) in spi.cs.iter().enumerate() {
+            pins.push((cs.pin.as_str(), format!("spi{n} (cs{m})")));
+        }
+    }
+    for device in &target.devices {
+        let name = &device.name;
+        pins.extend(
+            device
+                .cs_pin
+                .as_deref()
+                .map(|pin| (pin, format!("{name} (cs)"))),
+        );
+        for (n, pin) in device.int_pins.iter().enumerate() {
+            pins.push((pin.as_str(), format!("{name} (int{n})")));
+        }
+        pins.extend(
+            device
+                .enable_pin
+                .as_deref()
+                .map(|pin| (pin, format!("{name} (enable)"))),
+        );
+    }
+    for quirk in &target.quirks {
+        match quirk {
+            Quirk::SetPin(set_pin_op) => {
+                pins.push((set_pin_op.pin.as_str(), "set_pin quirk".to_string()));
+            }
+        }
+    }
+
+    pins
+}
+
+/// Checks all targets for which a chip description is available.
+pub fn check_sbd(sbd: &SbdFile) -> Result<()> {
+    let mut errors = Vec::new();
+
+    for target in sbd.targets.iter().flatten() {
+        if let Some(chip) = sbd.chip(&target.chip) {
+            errors.extend(
+                check_target(target, chip)
+                    .into_iter()
+                    .map(|e| format!("target `{}`: {e}", target.name)),
+            );
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!("{}", errors.join("\n"));
+    }
+
+    Ok(())
+}
+
+/// Checks that all pins used by a target exist on its chip, and that its buses' peripherals
+/// exist and can be routed to the bus' pins.
+pub fn check_target(target: &Target, chip: &Chip) -> Vec<String> {
+    let mut errors = Vec::new();
+    let chip_name = &chip.name;
+    let pins = ChipPins::new(Some(chip));
+
+    for (pin, user) in target_pins(target) {
+        match pins.parse(pin) {
+            Some((port, number)) if chip.has_gpio(port, number) => {}
+            Some(_) => errors.push(format!("{user}: pin {pin} does not exist on {chip_name}")),
+            // Maybe the name follows another vendor's naming scheme.
+            None => match ChipPins::default()
+                .parse(pin)
+                .and_then(|(port, number)| pins.format(port, number))
+            {
+                Some(name) => errors.push(format!(
+                    "{user}: pin {pin} does not follow the pin naming of {chip_name}, use {name}"
+                )),
+                None => errors.push(format!("{user}: cannot parse pin name {pin}")),
+            },
+        }
+    }
+
+    for bus in target_buses(target) {
+        let name = &bus.name;
+        for peripheral_name in bus.possible_peripherals {
+            let Some(peripheral) = chip.peripheral(peripheral_name) else {
+                errors.push(format!(
+                    "{name}: peripheral {peripheral_name} does not exist on {chip_name}"
+                ));
+                continue;
+            };
+
+            if peripheral.kind != bus.kind {
+                errors.push(format!(
+                    "{name}: peripheral {peripheral_name} is of kind {}, not {}",
+                    peripheral.kind.as_str(),
+                    bus.kind.as_str()
+                ));
+                continue;
+            }
+
+            for (signal, pin) in &bus.signals {
+                if peripheral.route(signal, pin).is_none() {
+                    errors.push(format!(
+                        "{name}: {signal} pin {pin} cannot be routed to {peripheral_name}"
+                    ));
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_target() {
+        let chip: Chip = serde_yaml::from_str(
+            "
+$key$: stm32
+pin_naming: port_underscore
+gpio_ports:
+  - port: 0
+    pins: 16
+peripherals:
+  USART1:
+    kind: uart
+    signals:
+      tx: [{ pin: P0_09, af: 7 }]
+      rx: [{ pin: P0_10, af: 7 }]
+  I2C1:
+    kind: i2c
+",
+        )
+        .unwrap();
+
+        let target: Target = serde_yaml::from_str(
+            "
+$key$: board
+chip: stm32
+leds:
+  - pin: P0_05
+  - pin: P1_05
+  - pin: P0.07
+uarts:
+  - rx_pin: P0_10
+    tx_pin: P0_09
+    possible_peripherals: [USART1]
+  - rx_pin: P0_03
+    tx_pin: P0_09
+    possible_peripherals: [USART1, I2C1, USART9]
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            check_target(&target, &chip),
+            [
+                "led1: pin P1_05 does not exist on stm32",
+                "led2: pin P0.07 does not follow the pin naming of stm32, use P0_07",
+                "uart1: rx pin P0_03 cannot be routed to USART1",
+                "uart1: peripheral I2C1 is of kind i2c, not uart",
+                "uart1: peripheral USART9 does not exist on stm32",
+            ]
+        );
+    }
+}
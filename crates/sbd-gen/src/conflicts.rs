@@ -0,0 +1,223 @@
+//! Detection of conflicting definitions across SBD files
+//!
+//! All files are merged into one, which lets e.g. a target defined in two files silently be
+//! overridden or combined, depending on file order. This tracks which file defined what, and
+//! reports definitions that collide.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{Result, bail};
+use serde_yaml::Value;
+
+/// What to do about conflicting definitions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Error,
+    #[default]
+    Warn,
+    Ignore,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(ConflictPolicy::Error),
+            "warn" => Ok(ConflictPolicy::Warn),
+            "ignore" => Ok(ConflictPolicy::Ignore),
+            _ => Err(format!("Invalid conflict policy: {s}")),
+        }
+    }
+}
+
+/// Maps that name entries which must only be defined once.
+const ENTRY_MAPS: [(&str, &str); 4] = [
+    ("targets", "target"),
+    ("chips", "chip"),
+    ("riot.chips", "RIOT chip"),
+    ("zephyr.chips", "Zephyr chip"),
+];
+
+/// Top-level keys that every file may set on its own.
+const PER_FILE_KEYS: [&str; 3] = ["version", "include", "description"];
+
+/// Definitions seen so far, and the conflicts among them.
+#[derive(Debug, Default)]
+pub struct Provenance {
+    /// Maps the path of a definition (e.g., `targets.nrf52840dk`) to the file defining it, and
+    /// for scalars, the value.
+    definitions: BTreeMap<String, (String, Option<Value>)>,
+    conflicts: Vec<String>,
+}
+
+impl Provenance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the definitions of a file, noting conflicts with earlier files.
+    pub fn record(&mut self, file: &str, value: &Value) {
+        let Some(mapping) = value.as_mapping() else {
+            return;
+        };
+
+        for (key, value) in mapping {
+            let Some(key) = key.as_str() else {
+                continue;
+            };
+            if !PER_FILE_KEYS.contains(&key) {
+                self.record_value(file, key, value);
+            }
+        }
+    }
+
+    fn record_value(&mut self, file: &str, path: &str, value: &Value) {
+        if let Some((_, kind)) = ENTRY_MAPS.iter().find(|(map, _)| *map == path) {
+            if let Some(entries) = value.as_mapping() {
+                for (name, value) in entries {
+                    let Some(name) = name.as_str() else {
+                        continue;
+                    };
+                    let entry = format!("{path}.{name}");
+                    if let Some((other, _)) = self.definitions.get(&entry) {
+                        self.conflicts.push(format!(
+                            "{kind} `{name}` is defined in both {other} and {file}"
+                        ));
+                    } else {
+                        self.definitions
+                            .insert(entry.clone(), (file.to_string(), None));
+                    }
+                    // Point out the values that differ between the definitions.
+                    self.record_value(file, &entry, value);
+                }
+            }
+            return;
+        }
+
+        match value {
+            Value::Mapping(mapping) => {
+                for (key, value) in mapping {
+                    if let Some(key) = key.as_str() {
+                        self.record_value(file, &format!("{path}.{key}"), value);
+                    }
+                }
+            }
+            // Lists are appended to each other, so they cannot conflict.
+            Value::Sequence(_) => {}
+            Value::Tagged(tagged) => self.record_value(file, path, &tagged.value),
+            scalar => {
+                if let Some((other, Some(other_value))) = self.definitions.get(path) {
+                    if other_value != scalar {
+                        self.conflicts.push(format!(
+                            "`{path}` is set to {} in {other}, but to {} in {file}",
+                            render(other_value),
+                            render(scalar)
+                        ));
+                    }
+                } else {
+                    self.definitions
+                        .insert(path.to_string(), (file.to_string(), Some(scalar.clone())));
+                }
+            }
+        }
+    }
+
+    /// Reports the conflicts according to `policy`.
+    pub fn report(&self, policy: ConflictPolicy) -> Result<()> {
+        if self.conflicts.is_empty() {
+            return Ok(());
+        }
+
+        match policy {
+            ConflictPolicy::Error => {
+                bail!(
+                    "conflicting definitions:\n  {}",
+                    self.conflicts.join("\n  ")
+                )
+            }
+            ConflictPolicy::Warn => {
+                for conflict in &self.conflicts {
+                    eprintln!("warning: {conflict}");
+                }
+            }
+            ConflictPolicy::Ignore => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn render(value: &Value) -> String {
+    serde_yaml::to_string(value)
+        .map_or_else(|_| format!("{value:?}"), |s| format!("`{}`", s.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflicts() {
+        let mut provenance = Provenance::new();
+        for (file, yaml) in [
+            (
+                "a.yaml",
+                "
+version: 0.4.0
+description: boards
+targets:
+  board-a:
+    chip: nrf52840
+riot:
+  chips:
+    nrf52840:
+      cpu: nrf52
+      cpu_model: nrf52840xxaa
+",
+            ),
+            (
+                "b.yaml",
+                "
+version: 0.4.8
+description: more boards
+targets:
+  board-a:
+    chip: nrf52833
+  board-b:
+    chip: nrf52840
+riot:
+  chips:
+    nrf52840:
+      cpu: nrf52
+      cpu_model: nrf52840
+ariel:
+  chips: [nrf52840]
+",
+            ),
+            (
+                "c.yaml",
+                "
+description: more boards
+ariel:
+  chips: [rp2040]
+",
+            ),
+        ] {
+            provenance.record(file, &serde_yaml::from_str(yaml).unwrap());
+        }
+
+        assert_eq!(
+            provenance.conflicts,
+            [
+                "target `board-a` is defined in both a.yaml and b.yaml",
+                "`targets.board-a.chip` is set to `nrf52840` in a.yaml, but to `nrf52833` in b.yaml",
+                "RIOT chip `nrf52840` is defined in both a.yaml and b.yaml",
+                "`riot.chips.nrf52840.cpu_model` is set to `nrf52840xxaa` in a.yaml, but to `nrf52840` in b.yaml",
+            ]
+        );
+
+        assert!(provenance.report(ConflictPolicy::Warn).is_ok());
+        assert!(provenance.report(ConflictPolicy::Error).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! On-board device handling shared between generators
+
+use anyhow::{Result, anyhow, bail};
+
+use sbd_gen_schema::{BusRef, Device, Target};
+
+use crate::resources::Resources;
+
+/// A device with its bus connection resolved.
+pub struct ResolvedDevice<'a> {
+    pub device: &'a Device,
+    pub bus: Option<BusRef<'a>>,
+    /// The chip-select pin, for devices on SPI buses.
+    pub cs_pin: Option<&'a str>,
+}
+
+impl ResolvedDevice<'_> {
+    /// Returns the name of the device as an identifier (see [`ident_part()`]).
+    pub fn ident(&self) -> String {
+        ident_part(&self.device.name)
+    }
+}
+
+/// Turns `name` into a part of an identifier, replacing all characters but `[A-Za-z0-9]` by `_`.
+///
+/// The result may start with a digit, so it needs a prefix to be a whole identifier.
+pub fn ident_part(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Resolves the bus a device is attached to and checks that the device carries the information
+/// needed for that bus.
+pub fn resolve_device<'a>(target: &'a Target, device: &'a Device) -> Result<ResolvedDevice<'a>> {
+    let name = &device.name;
+
+    let Some(bus_name) = &device.bus else {
+        return Ok(ResolvedDevice {
+            device,
+            bus: None,
+            cs_pin: device.cs_pin.as_deref(),
+        });
+    };
+
+    let bus = target
+        .find_bus(bus_name)
+        .ok_or_else(|| anyhow!("device `{name}`: unknown bus `{bus_name}`"))?;
+
+    let cs_pin = match bus {
+        BusRef::I2c(..) => {
+            if device.address.is_none() {
+                bail!("device `{name}`: on I2C bus `{bus_name}`, but has no `address`");
+            }
+            None
+        }
+        BusRef::Spi(_, spi) => {
+            // The CS pin may be given on the device or on the bus' CS list.
+            let cs_pin = device.cs_pin.as_deref().or_else(|| {
+                spi.cs
+                    .iter()
+                    .find(|cs| cs.device.as_ref() == Some(name))
+                    .map(|cs| cs.pin.as_str())
+            });
+            if cs_pin.is_none() {
+                bail!("device `{name}`: on SPI bus `{bus_name}`, but has no chip-select pin");
+            }
+            cs_pin
+        }
+    };
+
+    Ok(ResolvedDevice {
+        device,
+        bus: Some(bus),
+        cs_pin,
+    })
+}
+
+/// Claims the pins used by a device that are not already claimed by its bus.
+pub fn claim_device_pins<'a>(
+    resources: &mut Resources<'a>,
+    resolved: &ResolvedDevice<'a>,
+) -> Result<()> {
+    let name = &resolved.device.name;
+
+    if let Some(cs_pin) = resolved.cs_pin {
+        let claimed_by_bus = match resolved.bus {
+            Some(BusRef::Spi(_, spi)) => spi.cs.iter().any(|cs| cs.pin == cs_pin),
+            _ => false,
+        };
+        if !claimed_by_bus {
+            resources.claim(cs_pin, format!("{name} (cs)"))?;
+        }
+    }
+    for (n, int_pin) in resolved.device.int_pins.iter().enumerate() {
+        resources.claim(int_pin, format!("{name} (int{n})"))?;
+    }
+    if let Some(ref enable_pin) = resolved.device.enable_pin {
+        resources.claim(enable_pin, format!("{name} (enable)"))?;
+    }
+
+    Ok(())
+}
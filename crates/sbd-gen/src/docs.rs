@@ -0,0 +1,361 @@
+//! Board documentation generation
+
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use camino::Utf8PathBuf;
+
+use crate::{
+    conflicts::ConflictPolicy,
+    filemap::{ApplyOptions, FileMap, Mode, ReportFormat, parse_mode},
+    load_sbd_files,
+    manifest::Origin,
+};
+
+use sbd_gen_schema::{PinActive, PinLevel, Pull, Quirk, SbdFile, Target, Uart};
+
+#[derive(argh::FromArgs, Debug)]
+#[argh(subcommand, name = "generate-docs")]
+/// generate Markdown board documentation
+pub struct GenerateDocsArgs {
+    /// the name of the directory containing board descriptions
+    #[argh(positional)]
+    sbd_dir: String,
+
+    /// operation mode: create|check|update|dry-run
+    #[argh(option, short = 'm', from_str_fn(parse_mode))]
+    mode: Option<Mode>,
+
+    /// print diffs of the differing files (in check and dry-run mode)
+    #[argh(switch)]
+    diff: bool,
+
+    /// check mode result format: text|json (default: text)
+    #[argh(option, default = "ReportFormat::default()")]
+    format: ReportFormat,
+
+    /// write a junit xml report of the check mode results to this file
+    #[argh(option)]
+    junit: Option<Utf8PathBuf>,
+
+    /// overwrite files that were edited by hand (in update mode)
+    #[argh(switch)]
+    force: bool,
+
+    /// documentation output dir
+    #[argh(option, short = 'o', default = "Utf8PathBuf::from(\"board-docs\")")]
+    output: Utf8PathBuf,
+}
+
+pub fn generate(args: &GenerateDocsArgs, conflicts: ConflictPolicy) -> Result<()> {
+    let (sbd_file, inputs) = load_sbd_files(args.sbd_dir.as_str(), conflicts)?;
+    let mode = args.mode.unwrap_or_default();
+
+    let origin = Origin::new::<GenerateDocsArgs>(&args.sbd_dir, &args.output, &inputs)?;
+    let docs = render_docs_dir(&sbd_file).with_origin(origin);
+
+    let options = ApplyOptions {
+        diff: args.diff,
+        format: args.format,
+        junit: args.junit.clone(),
+        force: args.force,
+    };
+    mode.apply(&args.output, &docs, &options)?;
+
+    Ok(())
+}
+
+pub fn render_docs_dir(sbd: &SbdFile) -> FileMap {
+    let mut docs = FileMap::new();
+
+    let mut targets = sbd.targets.iter().flatten().collect::<Vec<_>>();
+    targets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    docs.insert("index.md".into(), render_index_md(sbd, &targets));
+    for target in targets {
+        docs.insert_owned(
+            format!("{}.md", target.name).into(),
+            render_target_md(target),
+            &target.name,
+        );
+    }
+
+    docs
+}
+
+fn render_index_md(sbd: &SbdFile, targets: &[&Target]) -> String {
+    let mut s = String::from("# Boards\n\n");
+
+    if let Some(ref description) = sbd.description {
+        let _ = writeln!(s, "{description}\n");
+    }
+
+    s.push_str("| Board | Chip | Description |\n");
+    s.push_str("| ----- | ---- | ----------- |\n");
+    for target in targets {
+        let _ = writeln!(
+            s,
+            "| [{name}]({name}.md) | {} | {} |",
+            cell(&target.chip),
+            cell(target.description.as_deref().unwrap_or_default()),
+            name = target.name,
+        );
+    }
+
+    s
+}
+
+fn render_target_md(target: &Target) -> String {
+    let mut s = String::new();
+
+    let _ = writeln!(s, "# {}\n", target.name);
+    if let Some(ref description) = target.description {
+        let _ = writeln!(s, "{description}\n");
+    }
+
+    let _ = writeln!(s, "- Chip: {}", target.chip);
+    if !target.flags.is_empty() {
+        let flags = target
+            .flags
+            .iter()
+            .map(|flag| format!("`{flag}`"))
+            .collect::<Vec<_>>();
+        let _ = writeln!(s, "- Flags: {}", flags.join(", "));
+    }
+
+    if target.has_leds() {
+        s.push_str("\n## LEDs\n\n");
+        s.push_str("| Name | Pin | Active | Color | Aliases |\n");
+        s.push_str("| ---- | --- | ------ | ----- | ------- |\n");
+        for (n, led) in target.leds.iter().enumerate() {
+            let _ = writeln!(
+                s,
+                "| led{n} | {} | {} | {} | {} |",
+                led.pin,
+                active(led.active),
+                cell(led.color.as_deref().unwrap_or_default()),
+                aliases(&led.aliases)
+            );
+        }
+    }
+
+    if target.has_buttons() {
+        s.push_str("\n## Buttons\n\n");
+        s.push_str("| Name | Pin | Active | Pull | Aliases |\n");
+        s.push_str("| ---- | --- | ------ | ---- | ------- |\n");
+        for (n, button) in target.buttons.iter().enumerate() {
+            let pull = match button.effective_pull() {
+                Pull::Up => "up",
+                Pull::Down => "down",
+                Pull::None => "none",
+            };
+            let _ = writeln!(
+                s,
+                "| button{n} | {} | {} | {pull} | {} |",
+                button.pin,
+                active(button.active),
+                aliases(&button.aliases)
+            );
+        }
+    }
+
+    render_buses_md(&mut s, target);
+
+    if target.has_devices() {
+        s.push_str("\n## Devices\n\n");
+        s.push_str("| Name | Type | Class | Bus | Description |\n");
+        s.push_str("| ---- | ---- | ----- | --- | ----------- |\n");
+        for device in &target.devices {
+            let _ = writeln!(
+                s,
+                "| {} | {} | {} | {} | {} |",
+                device.name,
+                device.type_,
+                device.class.as_str(),
+                device.bus.as_deref().unwrap_or_default(),
+                cell(device.description.as_deref().unwrap_or_default())
+            );
+        }
+    }
+
+    if !target.quirks.is_empty() {
+        s.push_str("\n## Quirks\n\n");
+        for quirk in &target.quirks {
+            match quirk {
+                Quirk::SetPin(set_pin_op) => {
+                    let level = match set_pin_op.level {
+                        PinLevel::High => "high",
+                        PinLevel::Low => "low",
+                    };
+                    let _ = write!(s, "- Pin {} is set {level} at startup", set_pin_op.pin);
+                    if let Some(ref description) = set_pin_op.description {
+                        let _ = write!(s, ": {description}");
+                    }
+                    s.push('\n');
+                }
+            }
+        }
+    }
+
+    if let Some(ref debugger) = target.debugger {
+        s.push_str("\n## Debugger\n\n");
+        let _ = writeln!(s, "- Type: {}", debugger.type_);
+        if let Some(ref uart) = debugger.uart {
+            let _ = writeln!(s, "- UART: RX {}, TX {}", uart.rx_pin, uart.tx_pin);
+        }
+    }
+
+    s
+}
+
+/// Renders the UART, I2C and SPI sections of a target page.
+fn render_buses_md(s: &mut String, target: &Target) {
+    if target.has_uarts() {
+        s.push_str("\n## UARTs\n\n");
+        s.push_str("| Name | RX | TX | CTS | RTS | Host facing | Aliases |\n");
+        s.push_str("| ---- | -- | -- | --- | --- | ----------- | ------- |\n");
+        if let Some(uart) = target.debugger_uart() {
+            render_uart_row(s, "debugger_uart", uart, true);
+        }
+        for (n, uart) in target.uarts.iter().enumerate() {
+            render_uart_row(s, &format!("uart{n}"), uart, uart.host_facing);
+        }
+    }
+
+    if target.has_i2cs() {
+        s.push_str("\n## I2C buses\n\n");
+        s.push_str("| Name | SDA | SCL | Frequency | Aliases |\n");
+        s.push_str("| ---- | --- | --- | --------- | ------- |\n");
+        for (n, i2c) in target.i2cs.iter().enumerate() {
+            let frequency = i2c.frequency.map(|f| format!("{f} Hz")).unwrap_or_default();
+            let _ = writeln!(
+                s,
+                "| i2c{n} | {} | {} | {frequency} | {} |",
+                i2c.sda_pin,
+                i2c.scl_pin,
+                aliases(&i2c.aliases)
+            );
+        }
+    }
+
+    if target.has_spis() {
+        s.push_str("\n## SPI buses\n\n");
+        s.push_str("| Name | SCK | MOSI | MISO | CS | Aliases |\n");
+        s.push_str("| ---- | --- | ---- | ---- | -- | ------- |\n");
+        for (n, spi) in target.spis.iter().enumerate() {
+            let cs = spi
+                .cs
+                .iter()
+                .map(|cs| match cs.device {
+                    Some(ref device) => format!("{} ({device})", cs.pin),
+                    None => cs.pin.clone(),
+                })
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                s,
+                "| spi{n} | {} | {} | {} | {} | {} |",
+                spi.sck_pin,
+                spi.mosi_pin.as_deref().unwrap_or_default(),
+                spi.miso_pin.as_deref().unwrap_or_default(),
+                cell(&cs.join(", ")),
+                aliases(&spi.aliases)
+            );
+        }
+    }
+}
+
+fn render_uart_row(s: &mut String, name: &str, uart: &Uart, host_facing: bool) {
+    let _ = writeln!(
+        s,
+        "| {name} | {} | {} | {} | {} | {} | {} |",
+        uart.rx_pin,
+        uart.tx_pin,
+        uart.cts_pin.as_deref().unwrap_or_default(),
+        uart.rts_pin.as_deref().unwrap_or_default(),
+        if host_facing { "yes" } else { "no" },
+        aliases(&uart.aliases)
+    );
+}
+
+fn active(active: Option<PinActive>) -> &'static str {
+    match active {
+        Some(PinActive::Low) => "low",
+        Some(PinActive::High) | None => "high",
+    }
+}
+
+fn aliases(aliases: &[String]) -> String {
+    cell(&aliases.join(", "))
+}
+
+/// Escapes text for use in a table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_target_md() {
+        use sbd_gen_schema::{Button, Debugger, Led, SetPinOp};
+
+        let target = Target {
+            description: Some("A test board".to_string()),
+            leds: vec![Led {
+                pin: "P0_13".to_owned(),
+                color: Some("green".to_owned()),
+                active: Some(PinActive::Low),
+                aliases: vec!["LED_GREEN".to_owned()],
+            }],
+            buttons: vec![Button {
+                pin: "P0_11".to_owned(),
+                active: Some(PinActive::Low),
+                aliases: vec![],
+                pull: None,
+                debounce_ms: None,
+            }],
+            quirks: vec![Quirk::SetPin(SetPinOp {
+                description: Some("enable the sensor supply".to_owned()),
+                pin: "P0_30".to_owned(),
+                level: PinLevel::High,
+            })],
+            debugger: Some(Debugger {
+                type_: "jlink".to_owned(),
+                uart: None,
+            }),
+            ..crate::ariel::test_default_target()
+        };
+
+        assert_eq!(
+            render_target_md(&target),
+            "# test-target
+
+A test board
+
+- Chip: test-chip
+
+## LEDs
+
+| Name | Pin | Active | Color | Aliases |
+| ---- | --- | ------ | ----- | ------- |
+| led0 | P0_13 | low | green | LED_GREEN |
+
+## Buttons
+
+| Name | Pin | Active | Pull | Aliases |
+| ---- | --- | ------ | ---- | ------- |
+| button0 | P0_11 | low | up |  |
+
+## Quirks
+
+- Pin P0_30 is set high at startup: enable the sensor supply
+
+## Debugger
+
+- Type: jlink
+"
+        );
+    }
+}
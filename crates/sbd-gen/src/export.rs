@@ -0,0 +1,180 @@
+//! Export of the merged board descriptions
+//!
+//! Writes the board descriptions as `sbd-gen` sees them after merging all files, resolving
+//! includes and `extends`, so other tools do not need to reimplement those semantics.
+
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use camino::Utf8PathBuf;
+use serde::Serialize;
+
+use sbd_gen_schema::{SbdFile, Target};
+
+use crate::{conflicts::ConflictPolicy, parse_sbd_files};
+
+#[derive(argh::FromArgs, Debug)]
+#[argh(subcommand, name = "export")]
+/// export the merged board descriptions as JSON or YAML
+pub struct ExportArgs {
+    /// the name of the directory containing board descriptions
+    #[argh(positional)]
+    sbd_dir: String,
+
+    /// output format: json|yaml (default: json)
+    #[argh(option, short = 'f', default = "Format::Json")]
+    format: Format,
+
+    /// only export this target (can be given multiple times)
+    #[argh(option, short = 't')]
+    target: Vec<String>,
+
+    /// output file (default: stdout)
+    #[argh(option, short = 'o')]
+    output: Option<Utf8PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Json,
+    Yaml,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            _ => Err(format!("Invalid format: {s}")),
+        }
+    }
+}
+
+/// Properties derived from a target's description.
+#[allow(clippy::struct_excessive_bools, reason = "these are independent flags")]
+#[derive(Debug, Serialize)]
+struct Capabilities {
+    has_leds: bool,
+    has_buttons: bool,
+    has_uarts: bool,
+    has_host_facing_uart: bool,
+    has_i2cs: bool,
+    has_spis: bool,
+    has_devices: bool,
+}
+
+impl Capabilities {
+    fn new(target: &Target) -> Self {
+        Self {
+            has_leds: target.has_leds(),
+            has_buttons: target.has_buttons(),
+            has_uarts: target.has_uarts(),
+            has_host_facing_uart: target.has_host_facing_uart(),
+            has_i2cs: target.has_i2cs(),
+            has_spis: target.has_spis(),
+            has_devices: target.has_devices(),
+        }
+    }
+}
+
+pub fn export(args: &ExportArgs, conflicts: ConflictPolicy) -> Result<()> {
+    let mut sbd_file = parse_sbd_files(args.sbd_dir.as_str(), conflicts)?;
+
+    if !args.target.is_empty() {
+        filter_targets(&mut sbd_file, &args.target)?;
+    }
+
+    let value = export_value(&sbd_file)?;
+    let mut output = match args.format {
+        Format::Json => serde_json::to_string_pretty(&value)?,
+        Format::Yaml => serde_yaml::to_string(&value)?,
+    };
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+
+    match args.output {
+        Some(ref path) => std::fs::write(path, output)?,
+        None => print!("{output}"),
+    }
+
+    Ok(())
+}
+
+/// Removes all targets not named in `names`.
+fn filter_targets(sbd: &mut SbdFile, names: &[String]) -> Result<()> {
+    let targets = sbd.targets.get_or_insert_default();
+
+    let unknown = names
+        .iter()
+        .filter(|name| !targets.iter().any(|target| &target.name == *name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        bail!("unknown target(s): {}", unknown.join(", "));
+    }
+
+    targets.retain(|target| names.contains(&target.name));
+
+    Ok(())
+}
+
+/// Serializes the board descriptions, adding each target's derived capabilities.
+fn export_value(sbd: &SbdFile) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(sbd)?;
+
+    if let Some(targets) = value
+        .get_mut("targets")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        for target in sbd.targets.iter().flatten() {
+            if let Some(serde_json::Value::Object(object)) = targets.get_mut(&target.name) {
+                object.insert(
+                    "capabilities".to_string(),
+                    serde_json::to_value(Capabilities::new(target))?,
+                );
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_value() {
+        let sbd: SbdFile = serde_yaml::from_str(
+            "
+targets:
+  board-a:
+    chip: nrf52840
+    leds:
+      - pin: P0_13
+  board-b:
+    chip: rp2040
+",
+        )
+        .unwrap();
+
+        let mut filtered = sbd.clone();
+        filter_targets(&mut filtered, &["board-a".to_string()]).unwrap();
+        let value = export_value(&filtered).unwrap();
+
+        let targets = value["targets"].as_object().unwrap();
+        assert_eq!(targets.keys().collect::<Vec<_>>(), ["board-a"]);
+        assert_eq!(targets["board-a"]["chip"], "nrf52840");
+        assert_eq!(targets["board-a"]["capabilities"]["has_leds"], true);
+        assert_eq!(
+            targets["board-a"]["capabilities"]["has_host_facing_uart"],
+            false
+        );
+
+        let err = filter_targets(&mut sbd.clone(), &["board-c".to_string()]).unwrap_err();
+        assert_eq!(err.to_string(), "unknown target(s): board-c");
+    }
+}
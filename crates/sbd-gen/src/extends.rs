@@ -0,0 +1,224 @@
+//! Target inheritance (`extends`)
+//!
+//! This operates on the merged YAML data, before it is deserialized into typed targets, so that
+//! derived targets only need to specify what differs from their base target.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, bail};
+use serde_yaml::{Mapping, Value};
+
+/// Resolves `extends` for all targets of a merged SBD file.
+pub fn resolve_extends(sbd: &mut Value) -> Result<()> {
+    let Some(targets) = sbd.get_mut("targets").and_then(Value::as_mapping_mut) else {
+        return Ok(());
+    };
+
+    let mut resolved = BTreeMap::new();
+    let names = targets
+        .keys()
+        .map(target_name)
+        .collect::<Result<Vec<_>>>()?;
+
+    for name in &names {
+        resolve_target(targets, name, &mut resolved, &mut Vec::new())?;
+    }
+
+    for (name, target) in resolved {
+        targets.insert(Value::String(name), target);
+    }
+
+    Ok(())
+}
+
+fn target_name(key: &Value) -> Result<String> {
+    key.as_str()
+        .map(ToString::to_string)
+        .ok_or_else(|| anyhow!("target name `{key:?}` is not a string"))
+}
+
+/// Resolves a single target, recursing into its base targets.
+///
+/// `stack` contains the targets currently being resolved, for cycle detection.
+fn resolve_target(
+    targets: &Mapping,
+    name: &str,
+    resolved: &mut BTreeMap<String, Value>,
+    stack: &mut Vec<String>,
+) -> Result<Value> {
+    if let Some(target) = resolved.get(name) {
+        return Ok(target.clone());
+    }
+
+    if stack.iter().any(|n| n == name) {
+        stack.push(name.to_string());
+        bail!("target inheritance cycle: {}", stack.join(" -> "));
+    }
+
+    let target = targets.get(name).ok_or_else(|| {
+        anyhow!(
+            "target `{}` extends unknown target `{name}`",
+            stack.last().unwrap()
+        )
+    })?;
+
+    let Some(base_name) = target.get("extends") else {
+        resolved.insert(name.to_string(), target.clone());
+        return Ok(target.clone());
+    };
+    let base_name = base_name
+        .as_str()
+        .ok_or_else(|| anyhow!("target `{name}`: `extends` must be a target name"))?;
+
+    stack.push(name.to_string());
+    let base = resolve_target(targets, base_name, resolved, stack)?;
+    stack.pop();
+
+    let merged = merge_target(&base, target)?;
+    resolved.insert(name.to_string(), merged.clone());
+
+    Ok(merged)
+}
+
+/// Merges a derived target into its (resolved) base target.
+fn merge_target(base: &Value, target: &Value) -> Result<Value> {
+    let (Some(base), Some(target)) = (base.as_mapping(), target.as_mapping()) else {
+        bail!("targets must be maps");
+    };
+
+    let replace = target
+        .get("replace")
+        .and_then(Value::as_sequence)
+        .map(|fields| fields.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut merged = base.clone();
+    // These only apply to the target they are set on.
+    merged.remove("extends");
+    merged.remove("replace");
+
+    for (key, value) in target {
+        let replaces = key.as_str().is_some_and(|key| replace.contains(&key));
+        match merged.get_mut(key) {
+            Some(base_value) if !replaces => merge_value(base_value, value),
+            _ => {
+                merged.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    Ok(Value::Mapping(merged))
+}
+
+/// Merges `value` into `base`: maps are merged recursively, lists are appended, anything else is
+/// overridden.
+pub fn merge_value(base: &mut Value, value: &Value) {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(value)) => {
+            for (key, value) in value {
+                match base.get_mut(key) {
+                    Some(base_value) => merge_value(base_value, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(value)) => {
+            base.extend(value.iter().cloned());
+        }
+        (base, value) => *base = value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(yaml: &str) -> Result<Value> {
+        let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+        resolve_extends(&mut value)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_extends() {
+        let value = resolve(
+            "
+targets:
+  base:
+    chip: nrf52840
+    flags: [a]
+    leds:
+      - pin: P0_13
+    uarts:
+      - rx_pin: P0_08
+        tx_pin: P0_06
+  rev2:
+    extends: base
+    flags: [b]
+    leds:
+      - pin: P0_14
+    replace: [uarts]
+    uarts:
+      - rx_pin: P1_01
+        tx_pin: P1_02
+",
+        )
+        .unwrap();
+
+        let expected: Value = serde_yaml::from_str(
+            "
+extends: base
+replace: [uarts]
+chip: nrf52840
+flags: [a, b]
+leds:
+  - pin: P0_13
+  - pin: P0_14
+uarts:
+  - rx_pin: P1_01
+    tx_pin: P1_02
+",
+        )
+        .unwrap();
+
+        assert_eq!(value["targets"]["rev2"], expected);
+    }
+
+    #[test]
+    fn test_extends_chain() {
+        let value = resolve(
+            "
+targets:
+  c:
+    extends: b
+    description: c
+  b:
+    extends: a
+    flags: [b]
+  a:
+    chip: nrf52840
+    description: a
+    flags: [a]
+",
+        )
+        .unwrap();
+
+        assert_eq!(value["targets"]["c"]["chip"], "nrf52840");
+        assert_eq!(value["targets"]["c"]["description"], "c");
+        assert_eq!(
+            value["targets"]["c"]["flags"],
+            serde_yaml::from_str::<Value>("[a, b]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_extends_errors() {
+        let err = resolve("targets: { a: { extends: b }, b: { extends: a } }").unwrap_err();
+        assert_eq!(err.to_string(), "target inheritance cycle: a -> b -> a");
+
+        let err = resolve("targets: { a: { extends: x } }").unwrap_err();
+        assert_eq!(err.to_string(), "target `a` extends unknown target `x`");
+    }
+}
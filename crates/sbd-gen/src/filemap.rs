@@ -1,16 +1,28 @@
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     default::Default,
     str::FromStr,
 };
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context as _, Result, anyhow};
 use camino::{Utf8Path, Utf8PathBuf};
 
+use crate::{
+    keep,
+    manifest::{Manifest, Origin},
+    report,
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct FileMap {
     pub map: BTreeMap<Utf8PathBuf, String>,
+    /// Maps files to the target they were generated for.
+    ///
+    /// Files shared by all targets have no owner.
+    owners: BTreeMap<Utf8PathBuf, String>,
     tagfile: Option<Utf8PathBuf>,
+    /// How the files are generated, recorded in the tag file.
+    origin: Origin,
 }
 
 impl FileMap {
@@ -31,21 +43,61 @@ impl FileMap {
         }
 
         if let Some(tagfile) = &self.tagfile {
-            std::fs::write(
-                outpath.join(tagfile),
-                "The contents of this directory were generated by sbd-gen.\n",
-            )?;
+            std::fs::write(outpath.join(tagfile), self.manifest().render()?)?;
         }
 
         Ok(())
     }
 
+    /// Brings `outpath` up to date, given the result of comparing it to this map.
+    ///
+    /// Only files that differ are touched. New contents are first written to temporary files
+    /// next to their destinations, which then replace the destinations by renaming. If anything
+    /// fails, all files are restored to their previous state.
+    pub fn update(&self, outpath: &Utf8Path, res: &CompareResult) -> Result<()> {
+        let mut transaction = Transaction::default();
+        let result = (|| {
+            for file in res.missing.iter().chain(&res.changed) {
+                transaction.stage(&outpath.join(file), &self.map[file])?;
+            }
+            if let Some(tagfile) = &self.tagfile {
+                let tagfile_path = outpath.join(tagfile);
+                let manifest = self.manifest().render()?;
+                if std::fs::read_to_string(&tagfile_path).ok().as_deref() != Some(manifest.as_str())
+                {
+                    transaction.stage(&tagfile_path, &manifest)?;
+                }
+            }
+
+            transaction.commit(&res.extra)
+        })();
+
+        match result {
+            Ok(()) => {
+                transaction.finish();
+                for leftover in &res.leftovers {
+                    let _ = std::fs::remove_file(leftover);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                transaction.rollback();
+                Err(e.context(format!(
+                    "updating `{outpath}` failed, no files were changed"
+                )))
+            }
+        }
+    }
+
     pub fn compare(&self, outpath: &Utf8Path) -> Result<CompareResult> {
         use walkdir::WalkDir;
         let mut missing = Vec::new();
         let mut changed = Vec::new();
         let mut extra = Vec::new();
+        let mut diffs = BTreeMap::new();
+        let mut leftovers = Vec::new();
         let mut all = HashSet::new();
+        let keep = keep::load_keep_list(outpath)?;
 
         for entry in WalkDir::new(outpath) {
             let entry = entry?;
@@ -56,27 +108,34 @@ impl FileMap {
                     })?;
 
                 let map_file_path = file_path.strip_prefix(outpath)?;
+                // Left behind by an interrupted update, not part of the output.
+                if is_leftover(map_file_path) {
+                    leftovers.push(file_path);
+                    continue;
+                }
                 all.insert(map_file_path.into());
 
                 if let Some(content) = self.map.get(map_file_path) {
-                    fn contents_match(file_path: &Utf8Path, content: &str) -> Result<bool> {
-                        let entry_content = std::fs::read_to_string(file_path)?;
-                        // TODO: hash?
-                        Ok(entry_content == content)
-                    }
-
-                    if entry.metadata()?.len() == content.len() as u64
-                        && contents_match(&file_path, content)?
-                    {
-                        // same
-                    } else {
+                    let existing = std::fs::read(&file_path)?;
+                    let existing = String::from_utf8_lossy(&existing);
+                    if existing != *content {
+                        diffs.insert(
+                            map_file_path.into(),
+                            unified_diff(map_file_path, &existing, content),
+                        );
                         changed.push(map_file_path.into());
                     }
                 } else if self
                     .tagfile
                     .as_ref()
                     .is_none_or(|tagfile| map_file_path != tagfile)
+                    && !keep.is_match(map_file_path)
                 {
+                    let existing = std::fs::read(&file_path)?;
+                    diffs.insert(
+                        map_file_path.into(),
+                        unified_diff(map_file_path, &String::from_utf8_lossy(&existing), ""),
+                    );
                     extra.push(file_path);
                 }
             }
@@ -88,20 +147,93 @@ impl FileMap {
             .collect::<HashSet<_>>()
             .difference(&all)
             .for_each(|file| {
+                diffs.insert(file.to_owned(), unified_diff(file, "", &self.map[file]));
                 missing.push(file.to_owned());
             });
+        missing.sort();
+
+        // Without a manifest (e.g., written by an older version), edits cannot be detected.
+        let mut edited = BTreeSet::new();
+        let mut changed_inputs = Vec::new();
+        if let Some(tagfile) = &self.tagfile
+            && let Some(manifest) = Manifest::read(&outpath.join(tagfile))?
+        {
+            for file in changed.iter().map(Utf8PathBuf::as_path).chain(
+                extra
+                    .iter()
+                    .map(|file| file.strip_prefix(outpath).unwrap_or(file)),
+            ) {
+                if manifest.is_edited(outpath, file)? {
+                    edited.insert(file.to_owned());
+                }
+            }
+            changed_inputs = manifest.changed_inputs(&self.origin);
+        }
 
         Ok(CompareResult {
             missing,
             extra,
             changed,
+            diffs,
+            edited,
+            changed_inputs,
+            leftovers,
         })
     }
 
+    /// Returns a copy of this map in which the user sections of the files existing in `outpath`
+    /// have been carried over.
+    pub(crate) fn with_user_sections(&self, outpath: &Utf8Path) -> Result<FileMap> {
+        let mut merged = self.clone();
+        for (file, content) in &mut merged.map {
+            if let Ok(existing) = std::fs::read_to_string(outpath.join(file)) {
+                *content = keep::merge_user_sections(file, &existing, content)?;
+            }
+        }
+        Ok(merged)
+    }
+
     pub(crate) fn insert(&mut self, from: Utf8PathBuf, manifest_content: String) {
         self.map.insert(from, manifest_content);
     }
 
+    /// Inserts a file generated for `target`.
+    pub(crate) fn insert_owned(&mut self, from: Utf8PathBuf, content: String, target: &str) {
+        self.owners.insert(from.clone(), target.to_string());
+        self.insert(from, content);
+    }
+
+    /// Marks all files as generated for `target`.
+    pub(crate) fn owned_by(mut self, target: &str) -> Self {
+        self.owners = self
+            .map
+            .keys()
+            .map(|file| (file.clone(), target.to_string()))
+            .collect();
+        self
+    }
+
+    /// Returns the target a file was generated for.
+    pub(crate) fn owner(&self, file: &Utf8Path) -> Option<&str> {
+        self.owners.get(file).map(String::as_str)
+    }
+
+    /// Returns all targets that own files.
+    pub(crate) fn targets(&self) -> BTreeSet<&str> {
+        self.owners.values().map(String::as_str).collect()
+    }
+
+    /// Sets how the files are generated.
+    pub(crate) fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Returns the manifest to record in the tag file.
+    fn manifest(&self) -> Manifest {
+        Manifest::new(&self.origin, &self.map)
+    }
+
     pub(crate) fn tagfile(&self) -> Option<Utf8PathBuf> {
         self.tagfile.clone()
     }
@@ -110,18 +242,188 @@ impl FileMap {
         let subdir = Utf8PathBuf::from(&name);
 
         for (file, content) in files.map {
-            let full_path = subdir.join(file);
+            let full_path = subdir.join(&file);
+            if let Some(owner) = files.owners.get(&file) {
+                self.owners.insert(full_path.clone(), owner.clone());
+            }
             self.insert(full_path, content);
         }
     }
 }
 
+/// File replacements that can be rolled back.
+#[derive(Debug, Default)]
+struct Transaction {
+    /// Temporary files holding new contents, with their destinations.
+    staged: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+    /// Destinations that have been replaced, with backups of their previous contents (if they
+    /// existed).
+    committed: Vec<(Utf8PathBuf, Option<Utf8PathBuf>)>,
+    /// Files that have been removed, with their backups.
+    removed: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+}
+
+impl Transaction {
+    /// Writes the new content of `file` to a temporary file next to it.
+    fn stage(&mut self, file: &Utf8Path, content: &str) -> Result<()> {
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = sibling(file, "tmp");
+        std::fs::write(&temp, content)?;
+        self.staged.push((temp, file.to_owned()));
+        Ok(())
+    }
+
+    /// Moves the staged files into place and removes `extra` files, keeping backups.
+    fn commit(&mut self, extra: &[Utf8PathBuf]) -> Result<()> {
+        // Entries stay staged until committed, so a rollback removes their temporary files.
+        while let Some((temp, file)) = self.staged.first() {
+            let backup = if file.exists() {
+                let backup = sibling(file, "bak");
+                backup_file(file, &backup)?;
+                Some(backup)
+            } else {
+                None
+            };
+            if let Err(e) = std::fs::rename(temp, file) {
+                if let Some(backup) = backup {
+                    let _ = std::fs::remove_file(backup);
+                }
+                return Err(anyhow!(e).context(format!("cannot write `{file}`")));
+            }
+            let (_, file) = self.staged.remove(0);
+            self.committed.push((file, backup));
+        }
+
+        for file in extra {
+            let backup = sibling(file, "bak");
+            std::fs::rename(file, &backup).with_context(|| format!("cannot remove `{file}`"))?;
+            self.removed.push((file.clone(), backup));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the backups.
+    fn finish(self) {
+        for backup in self
+            .committed
+            .into_iter()
+            .filter_map(|(_, backup)| backup)
+            .chain(self.removed.into_iter().map(|(_, backup)| backup))
+        {
+            let _ = std::fs::remove_file(backup);
+        }
+    }
+
+    /// Restores all files to their state before the transaction.
+    fn rollback(self) {
+        for (temp, _) in self.staged {
+            let _ = std::fs::remove_file(temp);
+        }
+        for (file, backup) in self.removed.into_iter().rev() {
+            let _ = std::fs::rename(backup, file);
+        }
+        for (file, backup) in self.committed.into_iter().rev() {
+            let _ = match backup {
+                Some(backup) => std::fs::rename(backup, file),
+                None => std::fs::remove_file(file),
+            };
+        }
+    }
+}
+
+/// Returns the path of a hidden file next to `file`.
+fn sibling(file: &Utf8Path, suffix: &str) -> Utf8PathBuf {
+    file.with_file_name(format!(
+        ".{}.sbd-gen-{suffix}",
+        file.file_name().unwrap_or_default()
+    ))
+}
+
+/// Returns whether `file` is a temporary or backup file of a [`Transaction`] (see [`sibling()`]).
+fn is_leftover(file: &Utf8Path) -> bool {
+    file.file_name().is_some_and(|name| {
+        name.starts_with('.') && (name.ends_with(".sbd-gen-tmp") || name.ends_with(".sbd-gen-bak"))
+    })
+}
+
+/// Keeps the content of `file` at `backup`, leaving `file` in place.
+fn backup_file(file: &Utf8Path, backup: &Utf8Path) -> Result<()> {
+    let _ = std::fs::remove_file(backup);
+    if std::fs::hard_link(file, backup).is_err() {
+        std::fs::copy(file, backup).with_context(|| format!("cannot back up `{file}`"))?;
+    }
+    Ok(())
+}
+
+/// Returns a unified diff from `old` to `new`.
+fn unified_diff(file: &Utf8Path, old: &str, new: &str) -> String {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("a/{file}"), &format!("b/{file}"))
+        .to_string()
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub enum Mode {
     #[default]
     Create,
     Update,
     Check,
+    /// Report what `update` would do, without writing anything.
+    DryRun,
+}
+
+/// Options for [`Mode::apply()`].
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    /// Print unified diffs of the files that differ (in `check` and `dry-run` mode).
+    pub diff: bool,
+    /// Format of the `check` mode results.
+    pub format: ReportFormat,
+    /// Write a `JUnit` XML report of the `check` mode results to this file.
+    pub junit: Option<Utf8PathBuf>,
+    /// Overwrite files that were edited by hand (in `update` mode).
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("Invalid format: {s}")),
+        }
+    }
+}
+
+/// How a file differs from its generated version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Missing,
+    Extra,
+    Changed,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileStatus::Missing => "missing",
+            FileStatus::Extra => "extra",
+            FileStatus::Changed => "changed",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -129,15 +431,70 @@ pub struct CompareResult {
     pub missing: Vec<Utf8PathBuf>,
     pub extra: Vec<Utf8PathBuf>,
     pub changed: Vec<Utf8PathBuf>,
+    /// Unified diffs from the existing to the generated files, keyed by path relative to the
+    /// output directory.
+    pub diffs: BTreeMap<Utf8PathBuf, String>,
+    /// Differing files that were edited by hand since they were generated, relative to the output
+    /// directory.
+    pub edited: BTreeSet<Utf8PathBuf>,
+    /// Input files that changed since the output was generated.
+    pub changed_inputs: Vec<String>,
+    /// Temporary and backup files left behind by an interrupted update, removed by the next
+    /// update.
+    pub leftovers: Vec<Utf8PathBuf>,
 }
 impl CompareResult {
-    fn is_same(&self) -> bool {
+    pub fn is_same(&self) -> bool {
         self.missing.is_empty() && self.extra.is_empty() && self.changed.is_empty()
     }
+
+    /// Returns all differing files, relative to the output directory.
+    pub fn entries<'a>(&'a self, outpath: &Utf8Path) -> Vec<(FileStatus, &'a Utf8Path)> {
+        let missing = self
+            .missing
+            .iter()
+            .map(|file| (FileStatus::Missing, file.as_path()));
+        let extra = self.extra.iter().map(|file| {
+            (
+                FileStatus::Extra,
+                file.strip_prefix(outpath).unwrap_or(file),
+            )
+        });
+        let changed = self
+            .changed
+            .iter()
+            .map(|file| (FileStatus::Changed, file.as_path()));
+
+        missing.chain(extra).chain(changed).collect()
+    }
+
+    /// Prints the differing files, labelled as given, and optionally their diffs.
+    fn print(&self, outpath: &Utf8Path, labels: [&str; 3], diff: bool) {
+        for (status, file) in self.entries(outpath) {
+            let label = match status {
+                FileStatus::Missing => labels[0],
+                FileStatus::Extra => labels[1],
+                FileStatus::Changed => labels[2],
+            };
+            if self.edited.contains(file) {
+                println!("{label}: {file} (edited by hand)");
+            } else {
+                println!("{label}: {file}");
+            }
+            if diff && let Some(diff) = self.diffs.get(file) {
+                print!("{diff}");
+            }
+        }
+    }
 }
 
 impl Mode {
-    pub fn apply(self, outpath: &Utf8Path, filemap: &FileMap) -> Result<()> {
+    pub fn apply(
+        self,
+        outpath: &Utf8Path,
+        filemap: &FileMap,
+        options: &ApplyOptions,
+    ) -> Result<()> {
         let tagfile = filemap.tagfile();
 
         // 1. create never overwrites
@@ -149,7 +506,7 @@ impl Mode {
                         "Directory already exists and is non-empty. Try `update` mode."
                     ));
                 }
-                Mode::Update => {
+                Mode::Update | Mode::DryRun => {
                     if let Some(ref tagfile) = tagfile {
                         let tagfile_path = outpath.join(tagfile);
                         if !tagfile_path.exists() {
@@ -163,31 +520,72 @@ impl Mode {
             }
         }
 
+        // Hand-written user sections of existing files are not differences.
+        let merged;
+        let filemap = if self != Mode::Create && outpath.exists() {
+            merged = filemap.with_user_sections(outpath)?;
+            &merged
+        } else {
+            filemap
+        };
+
         match self {
             Mode::Create => filemap.write_all(outpath)?,
             Mode::Check => {
                 let res = filemap.compare(outpath)?;
+                if let Some(ref junit) = options.junit {
+                    std::fs::write(junit, report::junit(&res, filemap, outpath))?;
+                }
+                if options.format == ReportFormat::Json {
+                    println!("{}", report::json(&res, filemap, outpath)?);
+                }
                 if !res.is_same() {
-                    for missing_file in res.missing {
-                        println!("missing: {missing_file}");
-                    }
-                    for extra_file in res.extra {
-                        println!("extra: {extra_file}");
-                    }
-                    for changed_file in res.changed {
-                        println!("changed: {changed_file}");
+                    if options.format == ReportFormat::Text {
+                        for input in &res.changed_inputs {
+                            println!("changed input: {input}");
+                        }
+                        res.print(outpath, ["missing", "extra", "changed"], options.diff);
                     }
-                    return Err(anyhow!(
-                        "Directory `{outpath}` is not up-to-date. Try `update` mode."
-                    ));
+                    let hint = if res.edited.is_empty() {
+                        "Try `update` mode."
+                    } else {
+                        "Try `update` mode with `--force` to discard the edits."
+                    };
+                    return Err(anyhow!("Directory `{outpath}` is not up-to-date. {hint}"));
                 }
             }
             Mode::Update => {
+                std::fs::create_dir_all(outpath)?;
                 let res = filemap.compare(outpath)?;
-                for file in res.extra {
-                    std::fs::remove_file(file)?;
+                if !res.edited.is_empty() && !options.force {
+                    let edited = res
+                        .edited
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                    return Err(anyhow!(
+                        "Refusing to overwrite files edited by hand: {}. Use `--force` to overwrite them.",
+                        edited.join(", ")
+                    ));
+                }
+                filemap.update(outpath, &res)?;
+            }
+            Mode::DryRun => {
+                if outpath.exists() {
+                    let res = filemap.compare(outpath)?;
+                    res.print(
+                        outpath,
+                        ["would create", "would remove", "would update"],
+                        options.diff,
+                    );
+                } else {
+                    for (file, content) in &filemap.map {
+                        println!("would create: {file}");
+                        if options.diff {
+                            print!("{}", unified_diff(file, "", content));
+                        }
+                    }
                 }
-                filemap.write_all(outpath)?;
             }
         }
         Ok(())
@@ -202,6 +600,7 @@ impl FromStr for Mode {
             "create" => Ok(Mode::Create),
             "update" => Ok(Mode::Update),
             "check" => Ok(Mode::Check),
+            "dry-run" => Ok(Mode::DryRun),
             _ => Err(format!("Invalid mode: {s}")),
         }
     }
@@ -210,3 +609,212 @@ impl FromStr for Mode {
 pub fn parse_mode(s: &str) -> Result<Mode, String> {
     Mode::from_str(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_diffs() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut filemap = FileMap::new();
+        filemap.insert("a.txt".into(), "one\ntwo\n".to_string());
+        filemap.insert("b.txt".into(), "new\n".to_string());
+        filemap.write_all(outpath).unwrap();
+
+        std::fs::write(outpath.join("a.txt"), "one\nthree\n").unwrap();
+        std::fs::remove_file(outpath.join("b.txt")).unwrap();
+        std::fs::write(outpath.join("c.txt"), "old\n").unwrap();
+
+        let res = filemap.compare(outpath).unwrap();
+        assert_eq!(res.changed, [Utf8PathBuf::from("a.txt")]);
+        assert_eq!(res.missing, [Utf8PathBuf::from("b.txt")]);
+        assert_eq!(res.extra, [outpath.join("c.txt")]);
+
+        assert_eq!(
+            res.diffs[Utf8Path::new("a.txt")],
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-three\n+two\n"
+        );
+        assert_eq!(
+            res.diffs[Utf8Path::new("b.txt")],
+            "--- a/b.txt\n+++ b/b.txt\n@@ -0,0 +1 @@\n+new\n"
+        );
+        assert_eq!(
+            res.diffs[Utf8Path::new("c.txt")],
+            "--- a/c.txt\n+++ b/c.txt\n@@ -1 +0,0 @@\n-old\n"
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut filemap = FileMap::new();
+        filemap.insert("a.txt".into(), "a\n".to_string());
+        filemap.insert("b.txt".into(), "b\n".to_string());
+        filemap.insert("c.txt".into(), "c\n".to_string());
+        filemap.write_all(outpath).unwrap();
+
+        let mtime = |file: &str| {
+            std::fs::metadata(outpath.join(file))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+        let b_mtime = mtime("b.txt");
+
+        filemap.map.remove(Utf8Path::new("c.txt"));
+        filemap.insert("a.txt".into(), "new a\n".to_string());
+        Mode::Update
+            .apply(outpath, &filemap, &ApplyOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(outpath.join("a.txt")).unwrap(),
+            "new a\n"
+        );
+        assert_eq!(mtime("b.txt"), b_mtime);
+        assert!(!outpath.join("c.txt").exists());
+        assert!(filemap.compare(outpath).unwrap().is_same());
+
+        // Hand edits are only overwritten when forced.
+        std::fs::write(outpath.join("b.txt"), "mine\n").unwrap();
+        std::fs::write(outpath.join("d.txt"), "mine\n").unwrap();
+        let res = filemap.compare(outpath).unwrap();
+        assert_eq!(res.edited, BTreeSet::from(["b.txt".into(), "d.txt".into()]));
+        assert!(
+            Mode::Update
+                .apply(outpath, &filemap, &ApplyOptions::default())
+                .is_err()
+        );
+        assert_eq!(
+            std::fs::read_to_string(outpath.join("b.txt")).unwrap(),
+            "mine\n"
+        );
+        let force = ApplyOptions {
+            force: true,
+            ..ApplyOptions::default()
+        };
+        Mode::Update.apply(outpath, &filemap, &force).unwrap();
+        assert!(filemap.compare(outpath).unwrap().is_same());
+    }
+
+    #[test]
+    fn test_update_keeps_user_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut filemap = FileMap::new();
+        filemap.insert(
+            "src/lib.rs".into(),
+            "// sbd-gen-user-begin: extra\n// sbd-gen-user-end: extra\n".to_string(),
+        );
+        filemap.insert("src/old.rs".into(), "old\n".to_string());
+        filemap.write_all(outpath).unwrap();
+        filemap.map.remove(Utf8Path::new("src/old.rs"));
+
+        std::fs::write(outpath.join(keep::KEEPFILE), "README.md\ndocs/\n").unwrap();
+        std::fs::write(outpath.join("README.md"), "readme\n").unwrap();
+        std::fs::create_dir(outpath.join("docs")).unwrap();
+        std::fs::write(outpath.join("docs/notes.md"), "notes\n").unwrap();
+        std::fs::write(
+            outpath.join("src/lib.rs"),
+            "// sbd-gen-user-begin: extra\nmod extra;\n// sbd-gen-user-end: extra\n",
+        )
+        .unwrap();
+
+        filemap.insert(
+            "src/lib.rs".into(),
+            "new\n// sbd-gen-user-begin: extra\n// sbd-gen-user-end: extra\n".to_string(),
+        );
+        Mode::Update
+            .apply(outpath, &filemap, &ApplyOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(outpath.join("src/lib.rs")).unwrap(),
+            "new\n// sbd-gen-user-begin: extra\nmod extra;\n// sbd-gen-user-end: extra\n"
+        );
+        assert!(outpath.join(keep::KEEPFILE).exists());
+        assert!(outpath.join("README.md").exists());
+        assert!(outpath.join("docs/notes.md").exists());
+        assert!(!outpath.join("src/old.rs").exists());
+        Mode::Check
+            .apply(outpath, &filemap, &ApplyOptions::default())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_compare_ignores_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut filemap = FileMap::new();
+        filemap.insert("a.txt".into(), "a\n".to_string());
+        filemap.insert("src/b.txt".into(), "b\n".to_string());
+        filemap.write_all(outpath).unwrap();
+
+        // As left behind by an update that was killed.
+        std::fs::write(outpath.join(".a.txt.sbd-gen-tmp"), "new a\n").unwrap();
+        std::fs::write(outpath.join("src/.b.txt.sbd-gen-bak"), "old b\n").unwrap();
+
+        let res = filemap.compare(outpath).unwrap();
+        assert!(res.is_same());
+        assert!(res.edited.is_empty());
+        assert_eq!(res.leftovers.len(), 2);
+        Mode::Check
+            .apply(outpath, &filemap, &ApplyOptions::default())
+            .unwrap();
+
+        // The next update cleans them up.
+        filemap.insert("a.txt".into(), "new a\n".to_string());
+        Mode::Update
+            .apply(outpath, &filemap, &ApplyOptions::default())
+            .unwrap();
+        assert!(!outpath.join(".a.txt.sbd-gen-tmp").exists());
+        assert!(!outpath.join("src/.b.txt.sbd-gen-bak").exists());
+        assert!(filemap.compare(outpath).unwrap().leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_update_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut filemap = FileMap::new();
+        filemap.insert("a.txt".into(), "a\n".to_string());
+        filemap.write_all(outpath).unwrap();
+
+        // `b.txt` cannot be written, as a directory is in the way.
+        std::fs::create_dir_all(outpath.join("b.txt/d")).unwrap();
+        std::fs::write(outpath.join("b.txt/d/x"), "x\n").unwrap();
+        filemap.insert("a.txt".into(), "new a\n".to_string());
+        filemap.insert("b.txt".into(), "b\n".to_string());
+
+        assert!(
+            Mode::Update
+                .apply(outpath, &filemap, &ApplyOptions::default())
+                .is_err()
+        );
+
+        assert_eq!(
+            std::fs::read_to_string(outpath.join("a.txt")).unwrap(),
+            "a\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(outpath.join("b.txt/d/x")).unwrap(),
+            "x\n"
+        );
+        let mut files = walkdir::WalkDir::new(outpath)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, [".sbd-gen", "a.txt", "x"]);
+    }
+}
@@ -0,0 +1,353 @@
+//! Handling of `include` on SBD files and targets
+//!
+//! - File level includes (`SbdFile::include`) name other SBD files, which are merged before the
+//!   including file. Each file is merged only once, so multiple files can include the same file.
+//! - Target level includes (`Target::include`) name fragment files containing target fields
+//!   (e.g., a common set of peripherals). The fragments are merged in include order, then the
+//!   target on top: lists are concatenated in that order (the target's own entries last), and
+//!   otherwise later values take precedence, the target's own values over all fragments.
+//!
+//! Include paths are relative to the including file.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde_yaml::{Mapping, Value};
+
+use crate::{check_version, extends::merge_value};
+
+/// Loads SBD files, resolving their includes.
+#[derive(Debug, Default)]
+pub struct Loader {
+    /// Canonical paths of the files that have already been loaded.
+    loaded: HashSet<PathBuf>,
+    /// Canonical paths of the files currently being loaded, for cycle detection.
+    stack: Vec<PathBuf>,
+    /// Maps a value (e.g., `targets.nrf52840dk`) to the files it was defined in.
+    pub sources: BTreeMap<String, Vec<String>>,
+    /// Paths of all files read, in reading order.
+    pub files: Vec<String>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads `file`, returning it and all files it includes as YAML documents, in merge order.
+    ///
+    /// Files that have been loaded before are skipped.
+    pub fn load(&mut self, file: &str) -> Result<Vec<(String, String)>> {
+        let canonical = canonicalize(file)?;
+        if self.loaded.contains(&canonical) {
+            return Ok(Vec::new());
+        }
+        self.enter(&canonical, file)?;
+
+        let yaml = std::fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+        self.add_file(file);
+        check_version(file, &yaml)?;
+
+        let mut value: Value =
+            serde_yaml::from_str(&yaml).with_context(|| format!("parsing file {file}"))?;
+
+        let mut documents = Vec::new();
+
+        // file level includes
+        for include in includes(&value).with_context(|| format!("in file {file}"))? {
+            let include = resolve_path(file, &include);
+            eprintln!("sbd: including '{include}' (from '{file}')");
+            documents.extend(
+                self.load(&include)
+                    .with_context(|| format!("included from {file}"))?,
+            );
+        }
+
+        // target level includes
+        if let Some(targets) = value.get_mut("targets").and_then(Value::as_mapping_mut) {
+            for (name, target) in targets.iter_mut() {
+                let name = name.as_str().unwrap_or_default();
+                self.add_source(&format!("targets.{name}"), file);
+
+                let fragments = includes(target).with_context(|| format!("in file {file}"))?;
+                if fragments.is_empty() {
+                    continue;
+                }
+
+                let mut merged = Value::Mapping(Mapping::new());
+                for fragment in fragments {
+                    let fragment = resolve_path(file, &fragment);
+                    let fragment_value = self
+                        .load_fragment(&fragment, name)
+                        .with_context(|| format!("included by target `{name}` in {file}"))?;
+                    merge_value(&mut merged, &fragment_value);
+                }
+                merge_value(&mut merged, target);
+                *target = merged;
+            }
+        }
+
+        if let Some(mapping) = value.as_mapping() {
+            for key in mapping.keys().filter_map(Value::as_str) {
+                if key != "targets" {
+                    self.add_source(key, file);
+                }
+            }
+        }
+
+        documents.push((file.to_string(), serde_yaml::to_string(&value)?));
+
+        self.leave();
+        self.loaded.insert(canonical);
+
+        Ok(documents)
+    }
+
+    /// Loads a target fragment, resolving the fragment's own includes.
+    fn load_fragment(&mut self, file: &str, target: &str) -> Result<Value> {
+        let canonical = canonicalize(file)?;
+        self.enter(&canonical, file)?;
+
+        eprintln!("sbd: including '{file}' into target '{target}'");
+        let yaml = std::fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+        self.add_file(file);
+        let mut value: Value =
+            serde_yaml::from_str(&yaml).with_context(|| format!("parsing file {file}"))?;
+        let Some(mapping) = value.as_mapping_mut() else {
+            bail!("target fragment {file} is not a map");
+        };
+
+        // Fragments may carry a version, but it is not a target field.
+        if mapping.remove("version").is_some() {
+            check_version(file, &yaml)?;
+        }
+
+        let mut merged = Value::Mapping(Mapping::new());
+        for include in includes(&value).with_context(|| format!("in file {file}"))? {
+            let include = resolve_path(file, &include);
+            let included = self
+                .load_fragment(&include, target)
+                .with_context(|| format!("included from {file}"))?;
+            merge_value(&mut merged, &included);
+        }
+        if let Some(mapping) = value.as_mapping_mut() {
+            mapping.remove("include");
+        }
+        merge_value(&mut merged, &value);
+
+        self.add_source(&format!("targets.{target}"), file);
+        self.leave();
+
+        Ok(merged)
+    }
+
+    fn enter(&mut self, canonical: &Path, file: &str) -> Result<()> {
+        if self.stack.iter().any(|f| f == canonical) {
+            let cycle = self
+                .stack
+                .iter()
+                .chain(std::iter::once(&canonical.to_path_buf()))
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>();
+            bail!("include cycle at {file}: {}", cycle.join(" -> "));
+        }
+        self.stack.push(canonical.to_path_buf());
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.stack.pop();
+    }
+
+    fn add_source(&mut self, key: &str, file: &str) {
+        let sources = self.sources.entry(key.to_string()).or_default();
+        if !sources.iter().any(|f| f == file) {
+            sources.push(file.to_string());
+        }
+    }
+
+    fn add_file(&mut self, file: &str) {
+        if !self.files.iter().any(|f| f == file) {
+            self.files.push(file.to_string());
+        }
+    }
+
+    /// Returns a human readable description of where a value was defined.
+    pub fn describe_sources(&self, key: &str) -> String {
+        self.sources
+            .get(key)
+            .map_or_else(|| "unknown file".to_string(), |files| files.join(", "))
+    }
+}
+
+/// Files included by other files.
+#[derive(Debug, Default)]
+pub struct IncludedFiles {
+    /// Canonical paths of included SBD files.
+    pub sbd_files: HashSet<PathBuf>,
+    /// Canonical paths of included target fragments.
+    pub fragments: HashSet<PathBuf>,
+}
+
+impl IncludedFiles {
+    /// Returns the files included by any of `files`.
+    ///
+    /// These are not merged on their own, but only where they are included.
+    pub fn scan(files: &[String]) -> Result<Self> {
+        let mut included = IncludedFiles::default();
+        let mut values = Vec::new();
+
+        for file in files {
+            let yaml = std::fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+            // Errors are reported when actually loading the file.
+            let Ok(value) = serde_yaml::from_str::<Value>(&yaml) else {
+                continue;
+            };
+
+            if let Some(targets) = value.get("targets").and_then(Value::as_mapping) {
+                for target in targets.values() {
+                    for include in includes(target).with_context(|| format!("in file {file}"))? {
+                        included.add_fragment(&resolve_path(file, &include))?;
+                    }
+                }
+            }
+
+            values.push((file, canonicalize(file)?, value));
+        }
+
+        // Includes in fragments name more fragments, but only fragments that are found through
+        // targets are known to be fragments, so this needs iterating.
+        loop {
+            let mut new_fragments = Vec::new();
+            for (file, canonical, value) in &values {
+                if included.fragments.contains(canonical) {
+                    for include in includes(value).with_context(|| format!("in file {file}"))? {
+                        let include = canonicalize(&resolve_path(file, &include))?;
+                        if !included.fragments.contains(&include) {
+                            new_fragments.push(include);
+                        }
+                    }
+                }
+            }
+            if new_fragments.is_empty() {
+                break;
+            }
+            included.fragments.extend(new_fragments);
+        }
+
+        for (file, canonical, value) in &values {
+            if !included.fragments.contains(canonical) {
+                for include in includes(value).with_context(|| format!("in file {file}"))? {
+                    let include = canonicalize(&resolve_path(file, &include))
+                        .with_context(|| format!("in file {file}"))?;
+                    included.sbd_files.insert(include);
+                }
+            }
+        }
+
+        Ok(included)
+    }
+
+    fn add_fragment(&mut self, file: &str) -> Result<()> {
+        self.fragments.insert(canonicalize(file)?);
+        Ok(())
+    }
+
+    /// Returns whether `file` is included by another file.
+    pub fn contains(&self, file: &str) -> Result<bool> {
+        let canonical = canonicalize(file)?;
+        Ok(self.sbd_files.contains(&canonical) || self.fragments.contains(&canonical))
+    }
+
+    /// Returns whether `file` is a target fragment.
+    pub fn is_fragment(&self, file: &str) -> Result<bool> {
+        Ok(self.fragments.contains(&canonicalize(file)?))
+    }
+}
+
+/// Returns the `include` list of a file or target.
+fn includes(value: &Value) -> Result<Vec<String>> {
+    let Some(include) = value.get("include") else {
+        return Ok(Vec::new());
+    };
+    if include.is_null() {
+        return Ok(Vec::new());
+    }
+
+    include
+        .as_sequence()
+        .ok_or_else(|| anyhow!("`include` must be a list of paths"))?
+        .iter()
+        .map(|path| {
+            path.as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| anyhow!("`include` must be a list of paths"))
+        })
+        .collect()
+}
+
+/// Resolves an include path relative to the including file.
+fn resolve_path(including_file: &str, include: &str) -> String {
+    let base = Path::new(including_file).parent().unwrap_or(Path::new(""));
+    base.join(include).to_string_lossy().into_owned()
+}
+
+fn canonicalize(file: &str) -> Result<PathBuf> {
+    std::fs::canonicalize(file).with_context(|| format!("cannot find file {file}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        write(
+            "one.yaml",
+            "leds:\n  - pin: P0_01\ndescription: one\nflags: [one]\n",
+        );
+        write(
+            "two.yaml",
+            "leds:\n  - pin: P0_02\ndescription: two\nflags: [two]\n",
+        );
+        let board = write(
+            "board.yaml",
+            "version: 0.4.0
+targets:
+  board:
+    chip: nrf52840
+    include: [one.yaml, two.yaml]
+    leds:
+      - pin: P0_03
+    flags: [own]
+",
+        );
+
+        let documents = Loader::new().load(&board).unwrap();
+        let value: Value = serde_yaml::from_str(&documents[0].1).unwrap();
+        let target = &value["targets"]["board"];
+
+        let leds: Vec<_> = target["leds"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|led| led["pin"].as_str().unwrap())
+            .collect();
+        assert_eq!(leds, ["P0_01", "P0_02", "P0_03"]);
+        assert_eq!(
+            target["flags"],
+            serde_yaml::from_str::<Value>("[one, two, own]").unwrap()
+        );
+        // The last fragment wins over earlier ones.
+        assert_eq!(target["description"], "two");
+    }
+}
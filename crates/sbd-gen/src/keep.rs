@@ -0,0 +1,259 @@
+//! Preservation of hand-written content in generated directories
+//!
+//! Two mechanisms let users extend generated output without it being clobbered by `update`:
+//!
+//! - A `.sbd-gen-keep` file in the output directory lists glob patterns (one per line, relative
+//!   to the output directory) of files that sbd-gen does not generate but must not remove.
+//! - Generated files may contain user sections, delimited by lines containing
+//!   `sbd-gen-user-begin: <name>` and `sbd-gen-user-end: <name>` (usually within a comment).
+//!   The content of such a section is carried over when the file is regenerated.
+//!
+//! The generators emit empty user sections where hand-written additions fit, e.g., for module
+//! declarations in the Ariel OS board crate's `src/lib.rs`, in its `[dependencies]`, and in each
+//! board's laze builder.
+
+use std::ops::Range;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use camino::Utf8Path;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// Name of the keep-list file, relative to the output directory.
+pub const KEEPFILE: &str = ".sbd-gen-keep";
+
+const BEGIN_MARKER: &str = "sbd-gen-user-begin:";
+const END_MARKER: &str = "sbd-gen-user-end:";
+
+/// Loads the keep-list of `outpath`.
+///
+/// The keep-list always matches the keep-list file itself. Empty lines and lines starting with
+/// `#` are ignored, and a pattern ending in `/` matches everything below that directory.
+pub fn load_keep_list(outpath: &Utf8Path) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    builder.add(GlobBuilder::new(KEEPFILE).literal_separator(true).build()?);
+
+    let path = outpath.join(KEEPFILE);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(anyhow!(e).context(format!("cannot read `{path}`"))),
+    };
+
+    for (n, line) in content.lines().enumerate() {
+        let pattern = line.trim();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            continue;
+        }
+        let pattern = if pattern.ends_with('/') {
+            format!("{pattern}**")
+        } else {
+            pattern.to_string()
+        };
+        let glob = GlobBuilder::new(&pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("{path}:{}: invalid pattern `{pattern}`", n + 1))?;
+        builder.add(glob);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Returns an empty user section called `name`, its markers following `comment` (which may
+/// start with indentation).
+pub fn user_section(comment: &str, name: &str) -> String {
+    format!("{comment} {BEGIN_MARKER} {name}\n{comment} {END_MARKER} {name}\n")
+}
+
+/// A user section, as line ranges into its file.
+#[derive(Debug)]
+struct UserSection<'a> {
+    name: &'a str,
+    /// Lines from the begin marker up to and including the end marker.
+    lines: Range<usize>,
+}
+
+impl UserSection<'_> {
+    /// Returns the lines between the markers.
+    fn body(&self) -> Range<usize> {
+        self.lines.start + 1..self.lines.end - 1
+    }
+}
+
+/// Returns the section name following `marker` in `line`, if any.
+fn marker_name<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let (_, rest) = line.split_once(marker)?;
+    // The name ends at whitespace, so that comment terminators (`*/`, `-->`) may follow.
+    Some(rest.split_whitespace().next().unwrap_or_default())
+}
+
+/// Finds the user sections in the lines of `file`.
+fn user_sections<'a>(file: &Utf8Path, lines: &[&'a str]) -> Result<Vec<UserSection<'a>>> {
+    let mut sections: Vec<UserSection<'a>> = Vec::new();
+    let mut open: Option<(&str, usize)> = None;
+
+    for (n, line) in lines.iter().enumerate() {
+        if let Some(name) = marker_name(line, BEGIN_MARKER) {
+            if let Some((open_name, _)) = open {
+                bail!(
+                    "{file}:{}: user section `{name}` starts within user section `{open_name}`",
+                    n + 1
+                );
+            }
+            if sections.iter().any(|section| section.name == name) {
+                bail!("{file}:{}: duplicate user section `{name}`", n + 1);
+            }
+            open = Some((name, n));
+        } else if let Some(name) = marker_name(line, END_MARKER) {
+            match open.take() {
+                Some((open_name, start)) if open_name == name => sections.push(UserSection {
+                    name,
+                    lines: start..n + 1,
+                }),
+                Some((open_name, _)) => bail!(
+                    "{file}:{}: user section `{open_name}` is ended as `{name}`",
+                    n + 1
+                ),
+                None => bail!(
+                    "{file}:{}: end of user section `{name}` without its begin",
+                    n + 1
+                ),
+            }
+        }
+    }
+
+    if let Some((name, start)) = open {
+        bail!("{file}:{}: user section `{name}` is never ended", start + 1);
+    }
+
+    Ok(sections)
+}
+
+/// Carries the user sections of the `existing` version of `file` over into its `generated`
+/// version.
+///
+/// Sections the generated version also contains get the existing content between their
+/// markers. Any other section is no longer generated (e.g., its board was removed): it is
+/// appended to the end of the file with a warning, so its content is not lost.
+pub fn merge_user_sections(file: &Utf8Path, existing: &str, generated: &str) -> Result<String> {
+    if !existing.contains(BEGIN_MARKER) {
+        return Ok(generated.to_string());
+    }
+
+    let existing_lines: Vec<_> = existing.split_inclusive('\n').collect();
+    let existing_sections = user_sections(file, &existing_lines)?;
+    let generated_lines: Vec<_> = generated.split_inclusive('\n').collect();
+    let generated_sections = user_sections(file, &generated_lines)
+        .with_context(|| format!("generated `{file}` has invalid user sections"))?;
+
+    let mut merged = String::with_capacity(existing.len().max(generated.len()));
+    let mut next = 0;
+    for section in &generated_sections {
+        let Some(existing_section) = existing_sections
+            .iter()
+            .find(|existing| existing.name == section.name)
+        else {
+            continue;
+        };
+        merged.extend(generated_lines[next..=section.lines.start].iter().copied());
+        merged.extend(existing_lines[existing_section.body()].iter().copied());
+        next = section.lines.end - 1;
+    }
+    merged.extend(generated_lines[next..].iter().copied());
+
+    for section in existing_sections.iter().filter(|existing| {
+        !generated_sections
+            .iter()
+            .any(|section| section.name == existing.name)
+    }) {
+        eprintln!(
+            "warning: {file}: user section `{}` is not generated anymore, moving it to the end of the file",
+            section.name
+        );
+        if !merged.is_empty() && !merged.ends_with('\n') {
+            merged.push('\n');
+        }
+        merged.extend(existing_lines[section.lines.clone()].iter().copied());
+    }
+
+    Ok(merged)
+}
+
+/// Returns `content` without the content of its user sections, keeping their markers.
+pub fn strip_user_sections(content: &str) -> String {
+    let mut in_section = false;
+    content
+        .split_inclusive('\n')
+        .filter(|line| {
+            if line.contains(BEGIN_MARKER) {
+                in_section = true;
+            } else if line.contains(END_MARKER) {
+                in_section = false;
+            } else {
+                return !in_section;
+            }
+            true
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_section() {
+        let section = user_section("  #", "board");
+        assert_eq!(
+            section,
+            "  # sbd-gen-user-begin: board\n  # sbd-gen-user-end: board\n"
+        );
+        let lines: Vec<_> = section.split_inclusive('\n').collect();
+        let sections = user_sections(Utf8Path::new("laze.yml"), &lines).unwrap();
+        assert_eq!(sections[0].name, "board");
+    }
+
+    #[test]
+    fn test_merge_user_sections() {
+        let file = Utf8Path::new("src/lib.rs");
+        let existing = "\
+old
+// sbd-gen-user-begin: imports
+use foo;
+// sbd-gen-user-end: imports
+/* sbd-gen-user-begin: extra */
+fn extra() {}
+/* sbd-gen-user-end: extra */
+";
+        let generated = "\
+new
+// sbd-gen-user-begin: imports
+// sbd-gen-user-end: imports
+generated";
+
+        assert_eq!(
+            merge_user_sections(file, existing, generated).unwrap(),
+            "\
+new
+// sbd-gen-user-begin: imports
+use foo;
+// sbd-gen-user-end: imports
+generated
+/* sbd-gen-user-begin: extra */
+fn extra() {}
+/* sbd-gen-user-end: extra */
+"
+        );
+
+        assert_eq!(
+            merge_user_sections(file, "no sections\n", generated).unwrap(),
+            generated
+        );
+
+        let err = merge_user_sections(file, "// sbd-gen-user-begin: a\n", generated).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "src/lib.rs:1: user section `a` is never ended"
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, HashMap};
 use camino::Utf8PathBuf;
 use serde::{Deserialize, Serialize};
 
-use crate::filemap::FileMap;
+use crate::{filemap::FileMap, keep};
 
 #[derive(Debug, Default)]
 pub struct Crate {
@@ -20,9 +20,12 @@ impl Crate {
     }
 
     pub fn render(mut self) -> FileMap {
+        // Dependencies are rendered as `[dependencies.<name>]` tables, so hand-written ones need
+        // their own `[dependencies]` table, which may follow the sub-tables.
         let manifest_content = format!(
-            "# @generated\n\n{}",
-            toml::to_string(&self.manifest).unwrap()
+            "# @generated\n\n{}\n[dependencies]\n{}",
+            toml::to_string(&self.manifest).unwrap(),
+            keep::user_section("#", "dependencies")
         );
 
         self.files
@@ -46,6 +49,7 @@ impl Crate {
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Manifest {
     pub package: Package,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub dependencies: BTreeMap<String, Dependency>,
     pub features: BTreeMap<String, Vec<String>>,
 }
@@ -10,6 +10,8 @@ use serde_with::skip_serializing_none;
 
 use sbd_gen_schema::common::StringOrVecString;
 
+use crate::keep;
+
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct LazeFile {
@@ -29,8 +31,28 @@ impl LazeFile {
         Ok(())
     }
 
-    pub fn to_string(&self) -> Result<String, serde_yaml::Error> {
-        serde_yaml::to_string(self)
+    /// Renders the file as YAML, with an empty user section, named like the context, at the end
+    /// of each context and builder.
+    pub fn to_string_with_user_sections(&self) -> Result<String, serde_yaml::Error> {
+        let mut s = String::new();
+        for (key, contexts) in [("contexts", &self.contexts), ("builders", &self.builders)] {
+            match contexts {
+                Some(contexts) if !contexts.is_empty() => {
+                    s.push_str(key);
+                    s.push_str(":\n");
+                    for context in contexts {
+                        s.push_str(&serde_yaml::to_string(std::slice::from_ref(context))?);
+                        s.push_str(&keep::user_section("  #", &context.name));
+                    }
+                }
+                Some(_) => {
+                    s.push_str(key);
+                    s.push_str(": []\n");
+                }
+                None => {}
+            }
+        }
+        Ok(s)
     }
 }
 
@@ -3,13 +3,30 @@ use sbd_gen_schema::{SbdFile, SbdFileVersion};
 use walkdir::WalkDir;
 use yaml_hash::YamlHash;
 
+use crate::conflicts::{ConflictPolicy, Provenance};
+
 mod ariel;
+mod chips;
+mod conflicts;
+mod devices;
+mod docs;
+mod export;
+mod extends;
 mod filemap;
+mod include;
+mod keep;
 mod krate;
 mod laze;
-mod pin2tuple;
+mod manifest;
+mod migrate;
+mod pin_naming;
+mod report;
 mod resources;
 mod riot;
+mod schema;
+mod solver;
+mod validate;
+mod zephyr;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -20,6 +37,11 @@ struct Args {
     #[argh(option, short = 'C')]
     chdir: Option<String>,
 
+    /// what to do when files define the same target, chip or value: error|warn|ignore
+    /// (default: warn)
+    #[argh(option, default = "ConflictPolicy::default()")]
+    conflicts: ConflictPolicy,
+
     /// print version and exit
     #[argh(switch, short = 'V')]
     version: bool,
@@ -33,6 +55,12 @@ struct Args {
 enum Subcommands {
     GenerateAriel(ariel::GenerateArielArgs),
     GenerateRiot(riot::GenerateRiotArgs),
+    GenerateZephyr(zephyr::GenerateZephyrArgs),
+    GenerateDocs(docs::GenerateDocsArgs),
+    Export(export::ExportArgs),
+    Schema(schema::SchemaArgs),
+    Migrate(migrate::MigrateArgs),
+    Validate(validate::ValidateArgs),
 }
 
 fn main() -> anyhow::Result<()> {
@@ -47,13 +75,20 @@ fn main() -> anyhow::Result<()> {
     }
 
     if let Some(dir) = args.chdir.as_ref() {
-        println!("sbd: changing to '{dir}'");
+        eprintln!("sbd: changing to '{dir}'");
         std::env::set_current_dir(dir)?;
     }
 
+    let conflicts = args.conflicts;
     match args.subcommand {
-        Some(Subcommands::GenerateAriel(args)) => ariel::generate(&args)?,
-        Some(Subcommands::GenerateRiot(args)) => riot::generate(&args)?,
+        Some(Subcommands::GenerateAriel(args)) => ariel::generate(&args, conflicts)?,
+        Some(Subcommands::GenerateRiot(args)) => riot::generate(&args, conflicts)?,
+        Some(Subcommands::GenerateZephyr(args)) => zephyr::generate(&args, conflicts)?,
+        Some(Subcommands::GenerateDocs(args)) => docs::generate(&args, conflicts)?,
+        Some(Subcommands::Export(args)) => export::export(&args, conflicts)?,
+        Some(Subcommands::Schema(args)) => schema::schema(&args)?,
+        Some(Subcommands::Migrate(args)) => migrate::migrate(&args)?,
+        Some(Subcommands::Validate(args)) => validate::validate(&args, conflicts)?,
         None => {
             println!("sbd: no subcommand given. try `sbd-gen --help`.");
         }
@@ -61,7 +96,8 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn parse_sbd_files(sbd_dir: &str) -> anyhow::Result<SbdFile> {
+/// Returns all `.yaml` files in `sbd_dir`, sorted.
+fn collect_sbd_files(sbd_dir: &str) -> Vec<String> {
     // Walk through the directory, collect all files ending with `.yaml`.
     // Then sort that list.
     let mut files = Vec::new();
@@ -79,22 +115,79 @@ fn parse_sbd_files(sbd_dir: &str) -> anyhow::Result<SbdFile> {
     }
     files.sort();
 
+    files
+}
+
+fn parse_sbd_files(sbd_dir: &str, conflicts: ConflictPolicy) -> anyhow::Result<SbdFile> {
+    load_sbd_files(sbd_dir, conflicts).map(|(sbd_file, _)| sbd_file)
+}
+
+/// Like [`parse_sbd_files()`], but also returns the paths of all files that were read.
+fn load_sbd_files(
+    sbd_dir: &str,
+    conflicts: ConflictPolicy,
+) -> anyhow::Result<(SbdFile, Vec<String>)> {
+    let files = collect_sbd_files(sbd_dir);
+
+    // Files included by other files are only merged where they are included.
+    let included = include::IncludedFiles::scan(&files)?;
+
     // Merge all the files into a single yaml object.
+    let mut loader = include::Loader::new();
     let mut hash = YamlHash::new();
+    let mut provenance = Provenance::new();
     for file in files {
-        println!("sbd: processing '{file}'");
-        let yaml = std::fs::read_to_string(&file)?;
-
-        check_version(&file, &yaml)?;
+        if included.contains(&file)? {
+            continue;
+        }
 
-        hash = hash.merge_str(&yaml)?;
+        eprintln!("sbd: processing '{file}'");
+        for (file, yaml) in loader.load(&file)? {
+            provenance.record(&file, &serde_yaml::from_str(&yaml)?);
+            hash = hash.merge_str(&yaml)?;
+        }
     }
+    provenance.report(conflicts)?;
 
     // Now do magic: serialize again, then deserialize into our known type.
     let merged = hash.to_string();
-    let sbd_file: SbdFile = serde_yaml::from_str(&merged).unwrap();
+    let mut sbd_value: serde_yaml::Value = serde_yaml::from_str(&merged)?;
+
+    extends::resolve_extends(&mut sbd_value)?;
+
+    let sbd_file: SbdFile = serde_yaml::from_value(sbd_value.clone())
+        .map_err(|e| locate_error(&sbd_value, &loader, e))?;
+
+    chips::check_sbd(&sbd_file)?;
+
+    Ok((sbd_file, loader.files))
+}
+
+/// Turns an error deserializing the merged data into one naming the files the failing target was
+/// defined in.
+fn locate_error(
+    sbd_value: &serde_yaml::Value,
+    loader: &include::Loader,
+    error: serde_yaml::Error,
+) -> anyhow::Error {
+    // Try the targets one by one to find the failing one.
+    if let Some(targets) = sbd_value.get("targets").and_then(|t| t.as_mapping()) {
+        for (name, target) in targets {
+            let mut single_target = serde_yaml::Mapping::new();
+            single_target.insert(name.clone(), target.clone());
+            let mut single = serde_yaml::Mapping::new();
+            single.insert("targets".into(), single_target.into());
+            if let Err(e) = serde_yaml::from_value::<SbdFile>(single.into()) {
+                let name = name.as_str().unwrap_or_default();
+                return anyhow::anyhow!(
+                    "target `{name}` (defined in {}): {e}",
+                    loader.describe_sources(&format!("targets.{name}"))
+                );
+            }
+        }
+    }
 
-    Ok(sbd_file)
+    anyhow::Error::new(error).context("parsing merged board descriptions")
 }
 
 fn check_version(file: &str, yaml: &str) -> anyhow::Result<()> {
@@ -102,8 +195,13 @@ fn check_version(file: &str, yaml: &str) -> anyhow::Result<()> {
         serde_yaml::from_str(yaml).with_context(|| format!("parsing file {file}"))?;
 
     if !version.is_compatible() {
+        let hint = if version.version < sbd_gen_schema::schema_version() {
+            " (try `sbd-gen migrate`)"
+        } else {
+            ""
+        };
         bail!(
-            "file {file} has version {}, which is not compatible with the used schema version {}",
+            "file {file} has version {}, which is not compatible with the used schema version {}{hint}",
             version.version,
             sbd_gen_schema::schema_version()
         );
@@ -118,7 +216,7 @@ mod tests {
 
     #[test]
     fn test_sbd_ariel() {
-        let sbd_file = parse_sbd_files("sbd-test-files").unwrap();
+        let sbd_file = parse_sbd_files("sbd-test-files", ConflictPolicy::Error).unwrap();
         let ariel = ariel::render_ariel_board_crate(&sbd_file).unwrap();
         insta::assert_debug_snapshot!(ariel);
     }
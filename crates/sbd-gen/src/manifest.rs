@@ -0,0 +1,295 @@
+//! The `.sbd-gen` manifest of generated directories
+//!
+//! Besides marking a directory as generated (so `update` may overwrite it), the manifest records
+//! how it was generated: the sbd-gen and schema versions, the command and its SBD directory, and
+//! hashes of the input and output files. This tells outputs that are outdated because their
+//! inputs changed apart from outputs that were edited by hand.
+//!
+//! Only what determines the output is recorded, so running the same generation again (e.g., in
+//! `check` mode, or from another working directory) leaves the manifest unchanged.
+//!
+//! Directories generated by older versions contain a plain sentence instead. That is still
+//! accepted as tag file, it just lacks the information.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context as _, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::{VERSION, keep};
+
+const HEADER: &str = "# The contents of this directory were generated by sbd-gen.\n";
+
+/// How a directory is being generated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origin {
+    /// Subcommand (e.g., `generate-riot`), without any options.
+    pub command: String,
+    /// Directory containing the SBD files, relative to the output directory.
+    pub sbd_dir: String,
+    /// Maps input files to hashes of their contents.
+    pub inputs: BTreeMap<String, String>,
+}
+
+impl Origin {
+    /// Describes the running subcommand `C`, which read `inputs` from `sbd_dir` to generate
+    /// `output`.
+    ///
+    /// Options that do not affect the output (e.g., `--diff`) are not recorded. Inputs within
+    /// `sbd_dir` are recorded relative to it, so moving the directory does not change them.
+    pub fn new<C: argh::SubCommand>(
+        sbd_dir: &str,
+        output: &Utf8Path,
+        inputs: &[String],
+    ) -> Result<Self> {
+        let inputs = inputs
+            .iter()
+            .map(|file| {
+                let content = std::fs::read(file).with_context(|| format!("reading {file}"))?;
+                let file = Utf8Path::new(file);
+                let name = file.strip_prefix(sbd_dir).unwrap_or(file);
+                Ok((name.to_string(), hash(&content)))
+            })
+            .collect::<Result<_>>()?;
+
+        let sbd_dir = relative_path(output.as_std_path(), Path::new(sbd_dir))?;
+        let sbd_dir = Utf8PathBuf::from_path_buf(sbd_dir)
+            .map_err(|path| anyhow!("non-utf8 path: \"{}\"", path.display()))?;
+
+        Ok(Self {
+            command: C::COMMAND.name.to_string(),
+            sbd_dir: sbd_dir.to_string(),
+            inputs,
+        })
+    }
+}
+
+/// Returns the path of `path` relative to the directory `base`, without accessing either.
+fn relative_path(base: &Path, path: &Path) -> Result<PathBuf> {
+    let normalize = |path: &Path| -> Result<Vec<_>> {
+        let mut components = Vec::new();
+        for component in std::path::absolute(path)?.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    components.pop();
+                }
+                component => components.push(component.as_os_str().to_owned()),
+            }
+        }
+        Ok(components)
+    };
+    let base = normalize(base)?;
+    let path = normalize(path)?;
+
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let mut relative: PathBuf =
+        std::iter::repeat_n(Component::ParentDir, base.len() - common).collect();
+    relative.extend(&path[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    Ok(relative)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    pub sbd_gen_version: String,
+    pub schema_version: String,
+    pub command: String,
+    /// Directory containing the SBD files, relative to the output directory.
+    #[serde(default)]
+    pub sbd_dir: String,
+    /// Maps input files to hashes of their contents.
+    pub inputs: BTreeMap<String, String>,
+    /// Maps generated files to hashes of their contents, leaving out user sections.
+    pub outputs: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Creates the manifest for `files` generated by `origin`.
+    pub fn new(origin: &Origin, files: &BTreeMap<Utf8PathBuf, String>) -> Self {
+        Self {
+            sbd_gen_version: VERSION.to_string(),
+            schema_version: sbd_gen_schema::schema_version().to_string(),
+            command: origin.command.clone(),
+            sbd_dir: origin.sbd_dir.clone(),
+            inputs: origin.inputs.clone(),
+            outputs: files
+                .iter()
+                .map(|(file, content)| (file.to_string(), output_hash(content)))
+                .collect(),
+        }
+    }
+
+    /// Renders the manifest as content of the tag file.
+    pub fn render(&self) -> Result<String> {
+        Ok(format!("{HEADER}{}", toml::to_string(self)?))
+    }
+
+    /// Reads the manifest from the tag file at `path`.
+    ///
+    /// Returns `None` if there is no tag file, or it was written by an older version.
+    pub fn read(path: &Utf8Path) -> Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow!(e).context(format!("cannot read `{path}`"))),
+        };
+        if !content.starts_with(HEADER) {
+            return Ok(None);
+        }
+
+        toml::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("parsing `{path}`"))
+    }
+
+    /// Returns whether `file` in `outpath` differs from what was generated.
+    ///
+    /// Files that were not generated count as edited. Changes within user sections do not.
+    pub fn is_edited(&self, outpath: &Utf8Path, file: &Utf8Path) -> Result<bool> {
+        let Some(recorded) = self.outputs.get(file.as_str()) else {
+            return Ok(true);
+        };
+        let content = std::fs::read(outpath.join(file))?;
+        Ok(output_hash(&String::from_utf8_lossy(&content)) != *recorded)
+    }
+
+    /// Returns the inputs that were added, removed or changed since generation.
+    pub fn changed_inputs(&self, origin: &Origin) -> Vec<String> {
+        let recorded = self
+            .inputs
+            .iter()
+            .filter(|(file, hash)| origin.inputs.get(*file) != Some(*hash))
+            .map(|(file, _)| file);
+        let added = origin
+            .inputs
+            .keys()
+            .filter(|file| !self.inputs.contains_key(*file));
+
+        recorded
+            .chain(added)
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+fn hash(content: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(content))
+}
+
+fn output_hash(content: &str) -> String {
+    hash(keep::strip_user_sections(content).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let origin = Origin {
+            command: "generate-riot".to_string(),
+            sbd_dir: "../sbd".to_string(),
+            inputs: BTreeMap::from([("sbd/a.yaml".to_string(), hash(b"a"))]),
+        };
+        let files = BTreeMap::from([
+            (Utf8PathBuf::from("a.txt"), "a\n".to_string()),
+            (
+                Utf8PathBuf::from("b.txt"),
+                "// sbd-gen-user-begin: b\n// sbd-gen-user-end: b\n".to_string(),
+            ),
+        ]);
+        for (file, content) in &files {
+            std::fs::write(outpath.join(file), content).unwrap();
+        }
+
+        let manifest = Manifest::new(&origin, &files);
+        let tagfile = outpath.join(".sbd-gen");
+        std::fs::write(&tagfile, manifest.render().unwrap()).unwrap();
+        assert_eq!(Manifest::read(&tagfile).unwrap().as_ref(), Some(&manifest));
+
+        std::fs::write(
+            outpath.join("b.txt"),
+            "// sbd-gen-user-begin: b\nmine\n// sbd-gen-user-end: b\n",
+        )
+        .unwrap();
+        assert!(!manifest.is_edited(outpath, "b.txt".into()).unwrap());
+        std::fs::write(outpath.join("a.txt"), "mine\n").unwrap();
+        assert!(manifest.is_edited(outpath, "a.txt".into()).unwrap());
+        assert!(manifest.is_edited(outpath, "c.txt".into()).unwrap());
+
+        let mut changed = origin.clone();
+        changed
+            .inputs
+            .insert("sbd/a.yaml".to_string(), hash(b"changed"));
+        changed.inputs.insert("sbd/b.yaml".to_string(), hash(b"b"));
+        assert_eq!(manifest.changed_inputs(&origin), Vec::<String>::new());
+        assert_eq!(
+            manifest.changed_inputs(&changed),
+            ["sbd/a.yaml", "sbd/b.yaml"]
+        );
+
+        std::fs::write(
+            &tagfile,
+            "The contents of this directory were generated by sbd-gen.\n",
+        )
+        .unwrap();
+        assert_eq!(Manifest::read(&tagfile).unwrap(), None);
+    }
+
+    #[test]
+    fn test_manifest_is_stable() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+        std::fs::create_dir_all(root.join("sbd")).unwrap();
+        std::fs::create_dir_all(root.join("out")).unwrap();
+        std::fs::write(root.join("sbd/a.yaml"), "a").unwrap();
+        let files = BTreeMap::from([(Utf8PathBuf::from("a.txt"), "a\n".to_string())]);
+
+        // The same generation, invoked from different places with different spellings.
+        let manifest = |sbd_dir: Utf8PathBuf, output: Utf8PathBuf| {
+            let input = sbd_dir.join("a.yaml").to_string();
+            let origin =
+                Origin::new::<crate::riot::GenerateRiotArgs>(sbd_dir.as_str(), &output, &[input])
+                    .unwrap();
+            Manifest::new(&origin, &files).render().unwrap()
+        };
+        let first = manifest(root.join("sbd"), root.join("out"));
+        assert!(first.contains("sbd-dir = \"../sbd\"\n"));
+        assert_eq!(
+            manifest(root.join("out/../sbd/"), root.join("./out")),
+            first
+        );
+        assert_eq!(manifest(root.join("sbd/."), root.join("out/")), first);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        for (base, path, relative) in [
+            ("/a/b", "/a/c", "../c"),
+            ("/a/b", "/a/b/c", "c"),
+            ("/a/b", "/a/b", "."),
+            ("/a/b/../c", "/a/./d/", "../d"),
+            ("/", "/a", "a"),
+        ] {
+            assert_eq!(
+                relative_path(Path::new(base), Path::new(path)).unwrap(),
+                Path::new(relative),
+                "{base} -> {path}"
+            );
+        }
+    }
+}
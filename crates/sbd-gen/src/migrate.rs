@@ -0,0 +1,421 @@
+//! Migration of SBD files to the current schema version
+//!
+//! Migrations are chained: a file is moved one schema version at a time until it is compatible
+//! with the current schema version. Each step transforms the parsed file; if none of them changes
+//! anything but the version, only the version line is replaced, so comments and formatting are
+//! kept. Otherwise, the file is rewritten from the transformed content, without its comments.
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use semver::{Version, VersionReq};
+use serde_yaml::{Mapping, Value};
+
+use sbd_gen_schema::SbdFileVersion;
+
+use crate::{collect_sbd_files, include::IncludedFiles};
+
+#[derive(argh::FromArgs, Debug)]
+#[argh(subcommand, name = "migrate")]
+/// migrate SBD files to the current schema version
+pub struct MigrateArgs {
+    /// the name of the directory containing board descriptions
+    #[argh(positional)]
+    sbd_dir: String,
+
+    /// only report the changes, don't write any files
+    #[argh(switch, short = 'n')]
+    dry_run: bool,
+}
+
+/// A migration step from one schema version to the next.
+#[derive(Debug)]
+struct Migration {
+    /// Files compatible with this version are migrated by this step.
+    from: Version,
+    to: Version,
+    /// Transforms a file (a target fragment if `is_fragment`), returning a description of each
+    /// change.
+    ///
+    /// Fails if the file cannot be migrated automatically.
+    migrate: fn(file: &mut Value, is_fragment: bool) -> Result<Vec<String>>,
+}
+
+/// All migration steps, oldest first.
+///
+/// A step's `to` is the first version of the compatible range it migrates into, not the current
+/// schema version (see [`sbd_gen_schema::schema_version()`]): e.g., migrated files are stamped
+/// `version: 0.4.0`, which is compatible with every 0.4.x schema version, so they are not tied to
+/// a newer `sbd-gen` than needed.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: Version::new(0, 2, 0),
+        to: Version::new(0, 3, 0),
+        migrate: rename_boards,
+    },
+    Migration {
+        from: Version::new(0, 3, 0),
+        to: Version::new(0, 4, 0),
+        migrate: unname_peripherals,
+    },
+];
+
+pub fn migrate(args: &MigrateArgs) -> Result<()> {
+    let files = collect_sbd_files(&args.sbd_dir);
+    let included = IncludedFiles::scan(&files)?;
+
+    let mut migrated = 0;
+    let mut failed = 0;
+    for file in &files {
+        let content = std::fs::read_to_string(file).with_context(|| format!("reading {file}"))?;
+
+        // Fragments only need a version if they have one.
+        let is_fragment = included.is_fragment(file)?;
+        if is_fragment && find_version_line(&content).is_none() {
+            continue;
+        }
+
+        let (new_content, changes) = match migrate_content(&content, is_fragment, MIGRATIONS) {
+            Ok(migrated) => migrated,
+            Err(e) => {
+                // The file is left as is, so it is not stamped with a version it does not match.
+                eprintln!("error: {file}: {e:#}");
+                failed += 1;
+                continue;
+            }
+        };
+        if changes.is_empty() {
+            continue;
+        }
+
+        println!("sbd: migrating '{file}'");
+        for change in &changes {
+            println!("  {change}");
+        }
+        if !args.dry_run {
+            std::fs::write(file, new_content).with_context(|| format!("writing {file}"))?;
+        }
+        migrated += 1;
+    }
+
+    if args.dry_run {
+        println!("sbd: {migrated} file(s) would be migrated");
+    } else {
+        println!("sbd: migrated {migrated} file(s)");
+    }
+    if failed > 0 {
+        bail!("{failed} file(s) could not be migrated");
+    }
+
+    Ok(())
+}
+
+/// Migrates the content of a file, returning the new content and a description of the changes.
+fn migrate_content(
+    content: &str,
+    is_fragment: bool,
+    migrations: &[Migration],
+) -> Result<(String, Vec<String>)> {
+    let file_version: SbdFileVersion = serde_yaml::from_str(content)?;
+    let from = file_version.version;
+
+    let mut version = from.clone();
+    let mut file: Value = serde_yaml::from_str(content)?;
+    let mut changes = Vec::new();
+
+    while !(SbdFileVersion {
+        version: version.clone(),
+    })
+    .is_compatible()
+    {
+        let Some(migration) = migrations
+            .iter()
+            .find(|migration| caret(&migration.from).matches(&version))
+        else {
+            bail!(
+                "no migration from version {version} to schema version {}",
+                sbd_gen_schema::schema_version()
+            );
+        };
+
+        changes.extend(
+            (migration.migrate)(&mut file, is_fragment).with_context(|| {
+                format!("cannot migrate from version {version} to {}", migration.to)
+            })?,
+        );
+        version = migration.to.clone();
+    }
+
+    if version == from {
+        return Ok((content.to_string(), changes));
+    }
+
+    let content = if changes.is_empty() {
+        set_version(content, &version)
+    } else {
+        changes.push("rewrote the file, comments are not kept".to_string());
+        set_version(&serde_yaml::to_string(&file)?, &version)
+    };
+    changes.push(format!("version {from} -> {version}"));
+
+    Ok((content, changes))
+}
+
+/// Migrates to 0.3.0, which renamed the top-level `boards` to `targets`.
+fn rename_boards(file: &mut Value, is_fragment: bool) -> Result<Vec<String>> {
+    let Some(mapping) = file.as_mapping_mut() else {
+        return Ok(Vec::new());
+    };
+    if is_fragment || !mapping.contains_key("boards") {
+        return Ok(Vec::new());
+    }
+    if mapping.contains_key("targets") {
+        bail!("both `boards` and `targets` are given");
+    }
+
+    // Rebuilt rather than removed and inserted, to keep the key order.
+    *mapping = std::mem::take(mapping)
+        .into_iter()
+        .map(|(key, value)| {
+            if key == "boards" {
+                (Value::from("targets"), value)
+            } else {
+                (key, value)
+            }
+        })
+        .collect();
+
+    Ok(vec!["renamed `boards` to `targets`".to_string()])
+}
+
+/// Migrates to 0.4.0, which made the names of LEDs, buttons and UARTs implicit: they are lists
+/// rather than maps, and the n-th item is named `ledN` (`buttonN`, `uartN`).
+fn unname_peripherals(file: &mut Value, is_fragment: bool) -> Result<Vec<String>> {
+    if is_fragment {
+        return unname_target_peripherals(file, "");
+    }
+
+    let mut changes = Vec::new();
+    if let Some(targets) = file.get_mut("targets").and_then(Value::as_mapping_mut) {
+        for (name, target) in targets {
+            let name = name.as_str().unwrap_or_default();
+            changes.extend(unname_target_peripherals(
+                target,
+                &format!("targets.{name}."),
+            )?);
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Turns the named LEDs, buttons and UARTs of a target into lists.
+///
+/// Only names that the list position reproduces can be migrated, i.e., `led0` to `ledN-1` for N
+/// LEDs.
+fn unname_target_peripherals(target: &mut Value, path: &str) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+
+    for (key, prefix) in [("leds", "led"), ("buttons", "button"), ("uarts", "uart")] {
+        let Some(value) = target.get_mut(key) else {
+            continue;
+        };
+        let Value::Mapping(named) = value else {
+            continue;
+        };
+        let named: Mapping = std::mem::take(named);
+
+        let mut items = vec![None; named.len()];
+        for (name, item) in named {
+            let name = name.as_str().unwrap_or_default();
+            let n = name
+                .strip_prefix(prefix)
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| format!("{prefix}{n}") == name && items.get(*n) == Some(&None))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{path}{key}: cannot keep the name `{name}`, {key} are named `{prefix}0` to `{prefix}{}` by their position now",
+                        items.len() - 1
+                    )
+                })?;
+            items[n] = Some(item);
+        }
+
+        *value = Value::Sequence(items.into_iter().flatten().collect());
+        changes.push(format!("{path}{key}: turned the named {key} into a list"));
+    }
+
+    Ok(changes)
+}
+
+/// Returns the requirement matching all versions compatible with `version`.
+fn caret(version: &Version) -> VersionReq {
+    // Any valid version is also a valid version requirement.
+    VersionReq::parse(&version.to_string()).unwrap()
+}
+
+/// Returns the index of the top-level `version` line.
+fn find_version_line(content: &str) -> Option<usize> {
+    content
+        .lines()
+        .position(|line| lazy_regex::regex_is_match!(r"^version\s*:", line))
+}
+
+/// Sets the top-level `version`, keeping a trailing comment.
+///
+/// Files without a version get one at the top, after leading comments.
+fn set_version(content: &str, version: &Version) -> String {
+    let mut lines = content.lines().map(str::to_string).collect::<Vec<_>>();
+
+    if let Some(n) = find_version_line(content) {
+        let comment = lazy_regex::regex_captures!(r"\s+(#.*)$", &lines[n])
+            .map(|(_, comment)| format!(" {comment}"))
+            .unwrap_or_default();
+        lines[n] = format!("version: {version}{comment}");
+    } else {
+        let n = lines
+            .iter()
+            .position(|line| {
+                let line = line.trim();
+                !(line.is_empty() || line.starts_with('#') || line == "---")
+            })
+            .unwrap_or(lines.len());
+        lines.insert(n, format!("version: {version}"));
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::validate::validate_file;
+
+    #[test]
+    fn test_migrate_0_2() {
+        let (content, changes) = migrate_content(
+            "# the DK
+version: 0.2.0
+boards:
+  nrf52840dk:
+    chip: nrf52840
+    leds:
+      led1:
+        pin: P0_14
+        color: green
+      led0:
+        pin: P0_13
+        color: green
+        active: low
+    uarts:
+      uart0:
+        rx_pin: P0_08
+        tx_pin: P0_06
+        host_facing: true
+",
+            false,
+            MIGRATIONS,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content,
+            "version: 0.4.0
+targets:
+  nrf52840dk:
+    chip: nrf52840
+    leds:
+    - pin: P0_13
+      color: green
+      active: low
+    - pin: P0_14
+      color: green
+    uarts:
+    - rx_pin: P0_08
+      tx_pin: P0_06
+      host_facing: true
+"
+        );
+        assert_eq!(
+            changes,
+            [
+                "renamed `boards` to `targets`",
+                "targets.nrf52840dk.leds: turned the named leds into a list",
+                "targets.nrf52840dk.uarts: turned the named uarts into a list",
+                "rewrote the file, comments are not kept",
+                "version 0.2.0 -> 0.4.0",
+            ]
+        );
+        assert!(validate_file("nrf52840dk.yaml", &content, false).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_0_3() {
+        let (content, changes) = migrate_content(
+            "version: 0.3.0
+targets:
+  nrf52840dk:
+    chip: nrf52840
+    buttons:
+      button0:
+        pin: P0_11
+        active: low
+",
+            false,
+            MIGRATIONS,
+        )
+        .unwrap();
+        assert_eq!(changes.last().unwrap(), "version 0.3.0 -> 0.4.0");
+        assert!(validate_file("nrf52840dk.yaml", &content, false).is_empty());
+
+        // Fragments have the peripherals at the top level.
+        let (content, _) = migrate_content(
+            "version: 0.3.0\nleds:\n  led0:\n    pin: P0_13\n",
+            true,
+            MIGRATIONS,
+        )
+        .unwrap();
+        assert_eq!(content, "version: 0.4.0\nleds:\n- pin: P0_13\n");
+        assert!(validate_file("fragment.yaml", &content, true).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_unnamed_failure() {
+        // The name cannot be kept, so the file is not migrated.
+        let content = "version: 0.3.0
+targets:
+  nrf52840dk:
+    chip: nrf52840
+    leds:
+      red:
+        pin: P0_13
+";
+        let error = migrate_content(content, false, MIGRATIONS).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "cannot migrate from version 0.3.0 to 0.4.0: targets.nrf52840dk.leds: cannot keep the name `red`, leds are named `led0` to `led0` by their position now"
+        );
+
+        let content = "version: 0.2.0\nboards: {}\ntargets: {}\n";
+        assert!(migrate_content(content, false, MIGRATIONS).is_err());
+    }
+
+    #[test]
+    fn test_migrate_up_to_date() {
+        let content = format!("version: {} # current\n", sbd_gen_schema::schema_version());
+        let (migrated, changes) = migrate_content(&content, false, MIGRATIONS).unwrap();
+        assert_eq!(migrated, content);
+        assert!(changes.is_empty());
+
+        // Without structural changes, comments are kept.
+        let (migrated, _) =
+            migrate_content("# old\nversion: 0.3.1 # old\n", false, MIGRATIONS).unwrap();
+        assert_eq!(migrated, "# old\nversion: 0.4.0 # old\n");
+        // The stamped version is older than, but compatible with, the current schema version.
+        let last = MIGRATIONS.last().unwrap();
+        assert!(caret(&last.to).matches(&sbd_gen_schema::schema_version()));
+
+        assert!(migrate_content("version: 1.0.0\n", false, MIGRATIONS).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Pin naming schemes
+//!
+//! Vendors name their pins differently (`P0_13`, `PA8`, `GPIO5`, ...). A chip declares its scheme
+//! in [`Chip::pin_naming`]; generators use [`ChipPins`] to convert pin names to (port, pin)
+//! tuples and back.
+
+use sbd_gen_schema::chip::{Chip, PinNaming};
+
+/// Conversion between pin names and (port, pin) tuples.
+pub trait PinNamingScheme {
+    /// Parses a pin name into a (port, pin) tuple.
+    fn parse(&self, name: &str) -> Option<(u8, u8)>;
+
+    /// Formats a (port, pin) tuple as pin name.
+    ///
+    /// Returns `None` if the scheme cannot name the pin (e.g., a port other than 0 for schemes
+    /// that only number pins).
+    fn format(&self, port: u8, pin: u8) -> Option<String>;
+}
+
+/// `P0_13`
+struct PortUnderscore;
+
+impl PinNamingScheme for PortUnderscore {
+    fn parse(&self, name: &str) -> Option<(u8, u8)> {
+        let (_, port, pin) = lazy_regex::regex_captures!(r"^P(\d+)_(\d+)$", name)?;
+        Some((port.parse().ok()?, pin.parse().ok()?))
+    }
+
+    fn format(&self, port: u8, pin: u8) -> Option<String> {
+        Some(format!("P{port}_{pin:02}"))
+    }
+}
+
+/// `P0.13`
+struct PortDot;
+
+impl PinNamingScheme for PortDot {
+    fn parse(&self, name: &str) -> Option<(u8, u8)> {
+        let (_, port, pin) = lazy_regex::regex_captures!(r"^P(\d+)\.(\d+)$", name)?;
+        Some((port.parse().ok()?, pin.parse().ok()?))
+    }
+
+    fn format(&self, port: u8, pin: u8) -> Option<String> {
+        Some(format!("P{port}.{pin:02}"))
+    }
+}
+
+/// `PA8`
+struct PortLetter;
+
+impl PinNamingScheme for PortLetter {
+    fn parse(&self, name: &str) -> Option<(u8, u8)> {
+        let (_, port, pin) = lazy_regex::regex_captures!(r"^P([A-Z])(\d+)$", name)?;
+        let port = port.as_bytes()[0] - b'A';
+        Some((port, pin.parse().ok()?))
+    }
+
+    fn format(&self, port: u8, pin: u8) -> Option<String> {
+        let port = char::from(b'A'.checked_add(port).filter(u8::is_ascii_uppercase)?);
+        Some(format!("P{port}{pin}"))
+    }
+}
+
+/// `PIN_25`
+struct Pin;
+
+impl PinNamingScheme for Pin {
+    fn parse(&self, name: &str) -> Option<(u8, u8)> {
+        let (_, pin) = lazy_regex::regex_captures!(r"^PIN_(\d+)$", name)?;
+        Some((0, pin.parse().ok()?))
+    }
+
+    fn format(&self, port: u8, pin: u8) -> Option<String> {
+        (port == 0).then(|| format!("PIN_{pin}"))
+    }
+}
+
+/// `GPIO5` or `GPIO_NUM_5`
+struct Gpio;
+
+impl PinNamingScheme for Gpio {
+    fn parse(&self, name: &str) -> Option<(u8, u8)> {
+        let (_, pin) = lazy_regex::regex_captures!(r"^GPIO(?:_NUM_)?(\d+)$", name)?;
+        Some((0, pin.parse().ok()?))
+    }
+
+    fn format(&self, port: u8, pin: u8) -> Option<String> {
+        (port == 0).then(|| format!("GPIO{pin}"))
+    }
+}
+
+/// `PIO0_12`
+struct Pio;
+
+impl PinNamingScheme for Pio {
+    fn parse(&self, name: &str) -> Option<(u8, u8)> {
+        let (_, port, pin) = lazy_regex::regex_captures!(r"^PIO(\d+)_(\d+)$", name)?;
+        Some((port.parse().ok()?, pin.parse().ok()?))
+    }
+
+    fn format(&self, port: u8, pin: u8) -> Option<String> {
+        Some(format!("PIO{port}_{pin}"))
+    }
+}
+
+/// Returns the implementation of a naming scheme.
+pub fn scheme(naming: PinNaming) -> &'static dyn PinNamingScheme {
+    match naming {
+        PinNaming::PortUnderscore => &PortUnderscore,
+        PinNaming::PortDot => &PortDot,
+        PinNaming::PortLetter => &PortLetter,
+        PinNaming::Pin => &Pin,
+        PinNaming::Gpio => &Gpio,
+        PinNaming::Pio => &Pio,
+    }
+}
+
+/// All known schemes, for chips that do not declare theirs.
+///
+/// The schemes' name formats do not overlap, so any name is understood by at most one of them.
+const ALL_SCHEMES: [PinNaming; 6] = [
+    PinNaming::PortUnderscore,
+    PinNaming::PortDot,
+    PinNaming::PortLetter,
+    PinNaming::Pin,
+    PinNaming::Gpio,
+    PinNaming::Pio,
+];
+
+/// Pin naming of a specific chip.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChipPins {
+    /// The chip's declared scheme, if any.
+    naming: Option<PinNaming>,
+}
+
+impl ChipPins {
+    pub fn new(chip: Option<&Chip>) -> Self {
+        Self {
+            naming: chip.and_then(|chip| chip.pin_naming),
+        }
+    }
+
+    /// Parses a pin name into a (port, pin) tuple.
+    ///
+    /// Without a declared scheme, any known scheme is accepted.
+    pub fn parse(self, name: &str) -> Option<(u8, u8)> {
+        match self.naming {
+            Some(naming) => scheme(naming).parse(name),
+            None => ALL_SCHEMES
+                .iter()
+                .find_map(|&naming| scheme(naming).parse(name)),
+        }
+    }
+
+    /// Formats a (port, pin) tuple as pin name.
+    ///
+    /// Without a declared scheme, `P<port>_<pin>` is used.
+    pub fn format(self, port: u8, pin: u8) -> Option<String> {
+        scheme(self.naming.unwrap_or(PinNaming::PortUnderscore)).format(port, pin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_schemes() {
+        let pins = ChipPins::default();
+        assert_eq!(pins.parse("P0_01"), Some((0, 1)));
+        assert_eq!(pins.parse("P12_34"), Some((12, 34)));
+        assert_eq!(pins.parse("P0.13"), Some((0, 13)));
+        assert_eq!(pins.parse("PA8"), Some((0, 8)));
+        assert_eq!(pins.parse("PB12"), Some((1, 12)));
+        assert_eq!(pins.parse("PIN_25"), Some((0, 25)));
+        assert_eq!(pins.parse("GPIO15"), Some((0, 15)));
+        assert_eq!(pins.parse("GPIO_NUM_5"), Some((0, 5)));
+        assert_eq!(pins.parse("PIO0_12"), Some((0, 12)));
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        let pins = ChipPins::default();
+        assert_eq!(pins.parse("P0_A1"), None);
+        assert_eq!(pins.parse("P_01"), None);
+        assert_eq!(pins.parse("GPIO_15"), None);
+        assert_eq!(pins.parse("p0_01"), None); // Case sensitive
+        assert_eq!(pins.parse("INVALID"), None);
+        assert_eq!(pins.parse("P0_300"), None);
+    }
+
+    #[test]
+    fn test_declared_scheme() {
+        let pins = ChipPins {
+            naming: Some(PinNaming::PortLetter),
+        };
+        assert_eq!(pins.parse("PC13"), Some((2, 13)));
+        assert_eq!(pins.parse("P0_13"), None);
+        assert_eq!(pins.format(2, 13).as_deref(), Some("PC13"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for naming in ALL_SCHEMES {
+            let scheme = scheme(naming);
+            let name = scheme.format(0, 7).unwrap();
+            assert_eq!(scheme.parse(&name), Some((0, 7)), "{name}");
+        }
+
+        assert_eq!(scheme(PinNaming::Gpio).format(1, 7), None);
+        assert_eq!(scheme(PinNaming::PortLetter).format(26, 0), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Machine-readable reports of `check` mode results
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use anyhow::Result;
+use camino::Utf8Path;
+use serde::Serialize;
+
+use crate::filemap::{CompareResult, FileMap};
+
+/// Name of the `JUnit` test case for files not generated for a specific target.
+const SHARED: &str = "(shared files)";
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    output: &'a str,
+    up_to_date: bool,
+    /// Input files that changed since the output was generated.
+    changed_inputs: &'a [String],
+    files: Vec<JsonFile<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFile<'a> {
+    path: &'a str,
+    status: &'static str,
+    /// Target the file was generated for, if any.
+    target: Option<&'a str>,
+    /// Whether the file was edited by hand since it was generated.
+    hand_edited: bool,
+    /// Unified diff from the existing to the generated file.
+    diff: Option<&'a str>,
+}
+
+/// Renders the results as JSON.
+pub fn json(res: &CompareResult, filemap: &FileMap, outpath: &Utf8Path) -> Result<String> {
+    let files = res
+        .entries(outpath)
+        .into_iter()
+        .map(|(status, file)| JsonFile {
+            path: file.as_str(),
+            status: status.as_str(),
+            target: filemap.owner(file),
+            hand_edited: res.edited.contains(file),
+            diff: res.diffs.get(file).map(String::as_str),
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&JsonReport {
+        output: outpath.as_str(),
+        up_to_date: res.is_same(),
+        changed_inputs: &res.changed_inputs,
+        files,
+    })?)
+}
+
+/// Renders the results as `JUnit` XML, with one test case per target.
+pub fn junit(res: &CompareResult, filemap: &FileMap, outpath: &Utf8Path) -> String {
+    let mut testcases: BTreeMap<&str, Vec<String>> = filemap
+        .targets()
+        .into_iter()
+        .map(|target| (target, Vec::new()))
+        .collect();
+    if filemap.map.keys().any(|file| filemap.owner(file).is_none()) {
+        testcases.insert(SHARED, Vec::new());
+    }
+
+    for (status, file) in res.entries(outpath) {
+        let mut failure = if res.edited.contains(file) {
+            format!("{}: {file} (edited by hand)\n", status.as_str())
+        } else {
+            format!("{}: {file}\n", status.as_str())
+        };
+        if let Some(diff) = res.diffs.get(file) {
+            failure.push_str(diff);
+        }
+        testcases
+            .entry(filemap.owner(file).unwrap_or(SHARED))
+            .or_default()
+            .push(failure);
+    }
+
+    let failures = testcases.values().filter(|f| !f.is_empty()).count();
+
+    let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    let _ = writeln!(
+        s,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\">",
+        escape(&format!("sbd-gen check {outpath}")),
+        testcases.len()
+    );
+    for (name, failures) in testcases {
+        let _ = write!(
+            s,
+            "    <testcase name=\"{}\" classname=\"{}\"",
+            escape(name),
+            escape(outpath.as_str())
+        );
+        if failures.is_empty() {
+            s.push_str("/>\n");
+            continue;
+        }
+        let _ = writeln!(
+            s,
+            ">\n      <failure message=\"{} file(s) not up-to-date\">{}</failure>\n    </testcase>",
+            failures.len(),
+            escape(&failures.concat())
+        );
+    }
+    s.push_str("  </testsuite>\n</testsuites>\n");
+
+    s
+}
+
+/// Escapes text for use in XML attributes and text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let outpath = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut filemap = FileMap::new();
+        filemap.insert("index.md".into(), "boards\n".to_string());
+        filemap.insert_owned("a.md".into(), "a & b\n".to_string(), "board-a");
+        filemap.insert_owned("b.md".into(), "b\n".to_string(), "board-b");
+        filemap.write_all(outpath).unwrap();
+        std::fs::write(outpath.join("a.md"), "a\n").unwrap();
+
+        let res = filemap.compare(outpath).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&json(&res, &filemap, outpath).unwrap()).unwrap();
+        assert_eq!(json["up_to_date"], false);
+        assert_eq!(json["changed_inputs"], serde_json::json!([]));
+        assert_eq!(
+            json["files"],
+            serde_json::json!([{
+                "path": "a.md",
+                "status": "changed",
+                "target": "board-a",
+                "hand_edited": true,
+                "diff": "--- a/a.md\n+++ b/a.md\n@@ -1 +1 @@\n-a\n+a & b\n",
+            }])
+        );
+
+        assert_eq!(
+            junit(&res, &filemap, outpath).replace(outpath.as_str(), "OUT"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="sbd-gen check OUT" tests="3" failures="1">
+    <testcase name="(shared files)" classname="OUT"/>
+    <testcase name="board-a" classname="OUT">
+      <failure message="1 file(s) not up-to-date">changed: a.md (edited by hand)
+--- a/a.md
++++ b/a.md
+@@ -1 +1 @@
+-a
++a &amp; b
+</failure>
+    </testcase>
+    <testcase name="board-b" classname="OUT"/>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -1,21 +1,50 @@
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use sbd_gen_schema::Target;
+use sbd_gen_schema::{Target, chip::Chip};
+
+use crate::{
+    chips::target_buses,
+    solver::{OsPeripherals, candidates, solve},
+};
 
 pub struct Resources<'a> {
     // Key names a resource, value is informational "claimed by".
     claims: HashMap<&'a str, String>,
+    // Key names a bus (e.g., `uart0`), value is the peripheral assigned to it.
+    peripherals: BTreeMap<String, String>,
 }
 
 impl<'a> Resources<'a> {
-    // (not using `_target` yet, keeping for future evolution)
-    pub fn new(_target: &Target) -> Self {
+    /// Creates the resources of a target, assigning peripherals to all its buses.
+    ///
+    /// `chip` (if described) restricts peripherals to those the bus' pins can be routed to, `os`
+    /// (if given) to those the OS knows about.
+    pub fn new(target: &Target, chip: Option<&Chip>, os: Option<&OsPeripherals>) -> Self {
+        let candidates = target_buses(target)
+            .iter()
+            .map(|bus| candidates(bus, chip, os))
+            .collect::<Vec<_>>();
+        // Buses left without a peripheral are skipped by the generators, with a warning.
+        let assignment = solve(&candidates);
+        for problem in &assignment.problems {
+            eprintln!("warning: {}: {problem}", target.name);
+        }
+        let peripherals = assignment.peripherals;
+
         Resources {
             claims: HashMap::new(),
+            peripherals,
         }
     }
 
+    /// Returns the peripheral assigned to a bus, if any.
+    ///
+    /// Buses are named like in [`crate::chips::Bus::name`], e.g., `uart0` or `debugger_uart`.
+    pub fn peripheral(&self, bus: &str) -> Option<&str> {
+        self.peripherals.get(bus).map(String::as_str)
+    }
+
     /// Claim a resource.
     ///
     /// This function is used to mark a resource, represented as `&'a str`. `by` is informational.
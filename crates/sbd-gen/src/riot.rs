@@ -1,13 +1,25 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::Write as _;
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context as _, Result, anyhow};
 use camino::Utf8PathBuf;
 
-use crate::filemap::{Mode, parse_mode};
-use crate::{filemap::FileMap, parse_sbd_files};
-
-use sbd_gen_schema::{SbdFile, Target};
+use crate::filemap::{ApplyOptions, Mode, ReportFormat, parse_mode};
+use crate::{
+    conflicts::ConflictPolicy,
+    devices::{ResolvedDevice, claim_device_pins, ident_part, resolve_device},
+    filemap::FileMap,
+    load_sbd_files,
+    manifest::Origin,
+    pin_naming::ChipPins,
+    resources::Resources,
+    solver::OsPeripherals,
+};
+
+use sbd_gen_schema::{
+    BusRef, DeviceClass, PinActive, PinLevel, Pull, Quirk, SbdFile, Target, chip::PeripheralKind,
+    common::Scalar, riot::RiotChipPeripherals,
+};
 
 #[derive(argh::FromArgs, Debug)]
 #[argh(subcommand, name = "generate-riot")]
@@ -17,10 +29,26 @@ pub struct GenerateRiotArgs {
     #[argh(positional)]
     sbd_dir: String,
 
-    /// operation mode: create|check|update
+    /// operation mode: create|check|update|dry-run
     #[argh(option, short = 'm', from_str_fn(parse_mode))]
     mode: Option<Mode>,
 
+    /// print diffs of the differing files (in check and dry-run mode)
+    #[argh(switch)]
+    diff: bool,
+
+    /// check mode result format: text|json (default: text)
+    #[argh(option, default = "ReportFormat::default()")]
+    format: ReportFormat,
+
+    /// write a junit xml report of the check mode results to this file
+    #[argh(option)]
+    junit: Option<Utf8PathBuf>,
+
+    /// overwrite files that were edited by hand (in update mode)
+    #[argh(switch)]
+    force: bool,
+
     /// riot os external boards output dir
     #[argh(option, short = 'o', default = "Utf8PathBuf::from(\"riot-os-boards\")")]
     output: Utf8PathBuf,
@@ -48,7 +76,6 @@ struct CFile {
 }
 
 impl CFile {
-    #[expect(dead_code, reason = "currently unused, unmark when it is")]
     pub fn new() -> Self {
         Self {
             ..Default::default()
@@ -69,7 +96,9 @@ impl CFile {
             s.push_str("#pragma once\n");
         }
         if !self.includes.is_empty() {
-            s.push('\n');
+            if !s.is_empty() {
+                s.push('\n');
+            }
             for include in &self.includes {
                 let _ = writeln!(s, "#include {include}");
             }
@@ -87,13 +116,20 @@ impl CFile {
     }
 }
 
-pub fn generate(args: &GenerateRiotArgs) -> Result<()> {
-    let sbd_file = parse_sbd_files(args.sbd_dir.as_str())?;
+pub fn generate(args: &GenerateRiotArgs, conflicts: ConflictPolicy) -> Result<()> {
+    let (sbd_file, inputs) = load_sbd_files(args.sbd_dir.as_str(), conflicts)?;
     let mode = args.mode.unwrap_or_default();
 
-    let boards_dir = render_riot_boards_dir(&sbd_file)?;
+    let origin = Origin::new::<GenerateRiotArgs>(&args.sbd_dir, &args.output, &inputs)?;
+    let boards_dir = render_riot_boards_dir(&sbd_file)?.with_origin(origin);
 
-    mode.apply(&args.output, &boards_dir)?;
+    let options = ApplyOptions {
+        diff: args.diff,
+        format: args.format,
+        junit: args.junit.clone(),
+        force: args.force,
+    };
+    mode.apply(&args.output, &boards_dir, &options)?;
 
     Ok(())
 }
@@ -109,7 +145,7 @@ pub fn render_riot_boards_dir(sbd: &SbdFile) -> Result<FileMap> {
         .collect::<HashSet<_>>();
 
     if chips.is_empty() {
-        println!("warning: No supported chips defined for RIOT OS");
+        eprintln!("warning: No supported chips defined for RIOT OS");
     }
 
     // filter targets with unknown chips
@@ -121,7 +157,7 @@ pub fn render_riot_boards_dir(sbd: &SbdFile) -> Result<FileMap> {
             if chips.contains(&target.chip) {
                 true
             } else {
-                println!(
+                eprintln!(
                     "warning: skipping target {}, unknown chip {}",
                     target.name, target.chip
                 );
@@ -132,18 +168,22 @@ pub fn render_riot_boards_dir(sbd: &SbdFile) -> Result<FileMap> {
         .collect::<Vec<_>>();
 
     if targets.is_empty() {
-        println!("warning: No targets defined for Riot OS");
+        eprintln!("warning: No targets defined for Riot OS");
     }
 
     let mut riot_targets = Vec::new();
 
     for target in targets {
-        riot_targets.push(generate_riot_target(sbd, &target)?);
+        riot_targets.push(
+            generate_riot_target(sbd, &target)
+                .with_context(|| anyhow!("cannot render {}", target.name))?,
+        );
     }
 
     let mut riot_boards_dir = FileMap::new();
     for target in riot_targets {
-        riot_boards_dir.extend_subdir(&Utf8PathBuf::from(target.name), target.files);
+        let files = target.files.owned_by(&target.name);
+        riot_boards_dir.extend_subdir(&Utf8PathBuf::from(target.name), files);
     }
 
     Ok(riot_boards_dir)
@@ -155,13 +195,14 @@ fn generate_riot_target(sbd: &SbdFile, target: &Target) -> Result<RiotTarget> {
 
     let mut periph_conf_h = CFile::new_header();
     let mut board_h = CFile::new_header();
+    let mut board_c = CFile::new();
 
     let mut makefile = String::new();
-    let makefile_dep = String::new();
+    let mut makefile_dep = String::new();
     let mut makefile_features = String::new();
     let mut makefile_include = String::new();
 
-    let mut features = HashSet::<String>::new();
+    let mut features = BTreeSet::<String>::new();
 
     // generate base headers
     periph_conf_h.includes.push("\"kernel_defines.h\"".into());
@@ -169,18 +210,28 @@ fn generate_riot_target(sbd: &SbdFile, target: &Target) -> Result<RiotTarget> {
 
     board_h.includes.push("\"cpu.h\"".into());
 
+    board_c.includes.push("\"board.h\"".into());
+    board_c.includes.push("\"periph/gpio.h\"".into());
+
     // populate makefiles
-    makefile.push_str("MODULE = board");
+    makefile.push_str("MODULE = board\n");
+    makefile.push_str("SRC = board.c\n");
 
     // both unwraps should always succeed (filtered in caller)
     let riot_chip = sbd.riot.as_ref().unwrap().chips.get(&target.chip).unwrap();
     let _ = writeln!(makefile_features, "CPU = {}", riot_chip.cpu);
     let _ = writeln!(makefile_features, "CPU_MODEL = {}", riot_chip.cpu_model);
 
+    let chip = sbd.chip(&target.chip);
+    let pins = ChipPins::new(chip);
+    let riot_peripherals = riot_chip.peripherals.clone().unwrap_or_default();
+    let mut resources = Resources::new(target, chip, Some(&os_peripherals(&riot_peripherals)));
+
     // handle file quirks
     let quirk_file_map = [
         ("periph_conf.h", &mut periph_conf_h),
         ("board.h", &mut board_h),
+        ("board.c", &mut board_c),
     ];
 
     for (filename, file_obj) in quirk_file_map {
@@ -191,78 +242,231 @@ fn generate_riot_target(sbd: &SbdFile, target: &Target) -> Result<RiotTarget> {
         }
     }
 
+    // LEDs and buttons
+    if target.has_leds() || target.has_buttons() {
+        board_h.includes.push("\"periph/gpio.h\"".into());
+        features.insert("periph_gpio".into());
+    }
+    if target.has_leds() {
+        board_h
+            .content_snips
+            .push(render_leds(target, pins, &mut resources)?);
+    }
+    if target.has_buttons() {
+        board_h
+            .content_snips
+            .push(render_buttons(target, pins, &mut resources)?);
+        features.insert("periph_gpio_irq".into());
+    }
+
     let mut uarts = Vec::new();
 
     // Debugger
     if let Some(debugger) = &target.debugger {
         let _ = writeln!(makefile_include, "PROGRAMMER ?= {}", debugger.type_);
         if let Some(uart) = &debugger.uart {
-            uarts.push(uart);
+            uarts.push(("debugger_uart".to_string(), uart));
         }
     }
 
     // UARTs
-    //let mut uart_isrs = Vec::new();
-    uarts.extend(target.uarts.iter());
-    let mut uart_peripherals = riot_chip.peripherals.as_ref().unwrap().uarts.clone();
-    let mut uarts_configured = Vec::new();
-    for (n, uart) in uarts.iter().enumerate() {
-        // get the map key of the peripheral that works for this UART's pins
-        #[expect(clippy::unnecessary_find_map)]
-        let peripheral_key = uart_peripherals
+    uarts.extend(
+        target
+            .uarts
             .iter()
-            .find_map(|(key, _peripheral)| {
-                // TODO: actually do filter/select by possible pins
-                Some(key)
-            })
-            .map(std::string::ToString::to_string)
-            .clone();
-
-        if let Some(key) = peripheral_key {
-            let uart_peripheral = uart_peripherals.remove(&key).unwrap();
+            .enumerate()
+            .map(|(n, uart)| (format!("uart{n}"), uart)),
+    );
+    let mut uarts_configured = Vec::new();
+    for (n, (bus, uart)) in uarts.iter().enumerate() {
+        let name = format!("UART{n}");
+        resources.claim(&uart.rx_pin, &name)?;
+        resources.claim(&uart.tx_pin, &name)?;
+
+        if let Some(uart_peripheral) = assigned_peripheral(&resources, bus, &riot_peripherals.uarts)
+        {
             let mut uart_cfg = uart_peripheral.config.clone();
-            let rx_pin = name2riot_pin(&uart.rx_pin)?;
-            let tx_pin = name2riot_pin(&uart.tx_pin)?;
+            let rx_pin = name2riot_pin(pins, &uart.rx_pin)?;
+            let tx_pin = name2riot_pin(pins, &uart.tx_pin)?;
             uart_cfg.insert("rx_pin".into(), rx_pin);
             uart_cfg.insert("tx_pin".into(), tx_pin);
 
-            uarts_configured.push((uart_cfg, uart_peripheral.isr));
+            uarts_configured.push((uart_cfg, uart_peripheral.isr.clone()));
         } else {
-            println!("warning: {}: no peripheral found for UART{n}", target.name);
+            eprintln!("warning: {}: no peripheral found for {name}", target.name);
         }
     }
 
     if !uarts_configured.is_empty() {
-        let mut s = String::new();
+        periph_conf_h
+            .content_snips
+            .push(render_periph_config("uart", &uarts_configured));
 
-        s.push('\n');
+        features.insert("periph_uart".into());
+    }
 
-        // generate cfg struct
+    // I2C buses
+    let mut i2cs_configured = Vec::new();
+    // maps the target's I2C buses to RIOT's `I2C_DEV(n)`
+    let mut i2c_devs = Vec::new();
+    for (n, i2c) in target.i2cs.iter().enumerate() {
+        let name = format!("I2C{n}");
+        resources.claim(&i2c.sda_pin, &name)?;
+        resources.claim(&i2c.scl_pin, &name)?;
+
+        if let Some(i2c_peripheral) =
+            assigned_peripheral(&resources, &format!("i2c{n}"), &riot_peripherals.i2cs)
         {
-            s.push_str("static const uart_conf_t uart_config[] = {\n");
-            for (uart_cfg, _) in &uarts_configured {
-                s.push_str("    {\n");
-                for (k, v) in uart_cfg {
-                    let _ = writeln!(s, "        .{k} = {v},");
+            let mut i2c_cfg = i2c_peripheral.config.clone();
+            i2c_cfg.insert("scl_pin".into(), name2riot_pin(pins, &i2c.scl_pin)?);
+            i2c_cfg.insert("sda_pin".into(), name2riot_pin(pins, &i2c.sda_pin)?);
+            if let Some(frequency) = i2c.frequency {
+                let (speed, speed_frequency) = riot_i2c_speed(frequency)
+                    .with_context(|| format!("{}: {name}", target.name))?;
+                if speed_frequency != frequency {
+                    eprintln!(
+                        "warning: {}: {name} frequency {frequency} Hz is not supported by RIOT, using {speed_frequency} Hz",
+                        target.name
+                    );
                 }
-                s.push_str("    },\n");
+                i2c_cfg.insert("speed".into(), speed.into());
             }
-            s.push_str("};\n\n");
+
+            i2c_devs.push(Some(i2cs_configured.len()));
+            i2cs_configured.push((i2c_cfg, i2c_peripheral.isr.clone()));
+        } else {
+            i2c_devs.push(None);
+            eprintln!("warning: {}: no peripheral found for {name}", target.name);
+        }
+    }
+
+    if !i2cs_configured.is_empty() {
+        periph_conf_h
+            .content_snips
+            .push(render_periph_config("i2c", &i2cs_configured));
+
+        features.insert("periph_i2c".into());
+    }
+
+    // SPI buses
+    let mut spis_configured = Vec::new();
+    let mut spi_cs_defines = String::new();
+    // maps the target's SPI buses to RIOT's `SPI_DEV(n)`
+    let mut spi_devs = Vec::new();
+    for (n, spi) in target.spis.iter().enumerate() {
+        let name = format!("SPI{n}");
+        resources.claim(&spi.sck_pin, &name)?;
+        if let Some(ref mosi_pin) = spi.mosi_pin {
+            resources.claim(mosi_pin, &name)?;
+        }
+        if let Some(ref miso_pin) = spi.miso_pin {
+            resources.claim(miso_pin, &name)?;
+        }
+        for (m, cs) in spi.cs.iter().enumerate() {
+            resources.claim(&cs.pin, format!("{name} (CS{m})"))?;
         }
 
-        for (n, (_, isr)) in uarts_configured.into_iter().enumerate() {
-            if let Some(isr) = isr {
-                let _ = writeln!(s, "#define UART_{n}_ISR          ({isr})");
+        if let Some(spi_peripheral) =
+            assigned_peripheral(&resources, &format!("spi{n}"), &riot_peripherals.spis)
+        {
+            let dev = spis_configured.len();
+            let mut spi_cfg = spi_peripheral.config.clone();
+            spi_cfg.insert("sclk_pin".into(), name2riot_pin(pins, &spi.sck_pin)?);
+            spi_cfg.insert(
+                "mosi_pin".into(),
+                optional_riot_pin(pins, spi.mosi_pin.as_deref())?,
+            );
+            spi_cfg.insert(
+                "miso_pin".into(),
+                optional_riot_pin(pins, spi.miso_pin.as_deref())?,
+            );
+
+            for (m, cs) in spi.cs.iter().enumerate() {
+                let _ = write!(
+                    spi_cs_defines,
+                    "#define SPI{dev}_CS{m}_PIN          {}",
+                    name2riot_pin(pins, &cs.pin)?
+                );
+                if let Some(ref device) = cs.device {
+                    let _ = write!(spi_cs_defines, " /**< {device} */");
+                }
+                spi_cs_defines.push('\n');
             }
+
+            spi_devs.push(Some(dev));
+            spis_configured.push((spi_cfg, spi_peripheral.isr.clone()));
+        } else {
+            spi_devs.push(None);
+            eprintln!("warning: {}: no peripheral found for {name}", target.name);
         }
+    }
 
-        s.push_str("#define UART_NUMOF          ARRAY_SIZE(uart_config)\n\n");
+    if !spis_configured.is_empty() {
+        periph_conf_h
+            .content_snips
+            .push(render_periph_config("spi", &spis_configured));
 
-        periph_conf_h.content_snips.push(s);
+        if !spi_cs_defines.is_empty() {
+            board_h.content_snips.push(format!("{spi_cs_defines}\n"));
+        }
 
-        features.insert("periph_uart".into());
+        features.insert("periph_spi".into());
     }
 
+    // on-board devices
+    let mut device_types = BTreeSet::new();
+    // maps a module dependency (if any) to the device driver modules it pulls in
+    let mut device_modules = BTreeMap::<Option<&str>, BTreeSet<&str>>::new();
+    for device in &target.devices {
+        let resolved = resolve_device(target, device)?;
+        claim_device_pins(&mut resources, &resolved)?;
+
+        let dependency = match device.class {
+            DeviceClass::Sensor => Some("saul_default"),
+            DeviceClass::Flash => Some("mtd"),
+            DeviceClass::Radio => Some("netdev_default"),
+            DeviceClass::Other => None,
+        };
+        device_modules
+            .entry(dependency)
+            .or_default()
+            .insert(&device.type_);
+
+        // RIOT's `*_params.h` only support one default configuration per driver.
+        if !device_types.insert(&device.type_) {
+            eprintln!(
+                "warning: {}: multiple devices of type `{}`, only the first gets default parameters",
+                target.name, device.type_
+            );
+            continue;
+        }
+
+        board_h
+            .content_snips
+            .push(render_device_params(&resolved, pins, &i2c_devs, &spi_devs)?);
+    }
+
+    for (dependency, modules) in device_modules {
+        let indent = if let Some(dependency) = dependency {
+            let _ = writeln!(makefile_dep, "ifneq (,$(filter {dependency},$(USEMODULE)))");
+            "  "
+        } else {
+            ""
+        };
+        for module in modules {
+            let _ = writeln!(makefile_dep, "{indent}USEMODULE += {module}");
+        }
+        if dependency.is_some() {
+            makefile_dep.push_str("endif\n");
+        }
+    }
+
+    // board initialization
+    if !target.quirks.is_empty() {
+        features.insert("periph_gpio".into());
+    }
+    board_c.content_snips.push(render_board_init(target, pins)?);
+
     // finishing
     if !features.is_empty() {
         for feature in features {
@@ -272,6 +476,8 @@ fn generate_riot_target(sbd: &SbdFile, target: &Target) -> Result<RiotTarget> {
 
     makefile.push_str("\ninclude $(RIOTBASE)/Makefile.base\n");
 
+    riot_target.files.insert("board.c".into(), board_c.render());
+
     riot_target
         .files
         .insert("include/periph_conf.h".into(), periph_conf_h.render());
@@ -292,9 +498,520 @@ fn generate_riot_target(sbd: &SbdFile, target: &Target) -> Result<RiotTarget> {
     Ok(riot_target)
 }
 
-fn name2riot_pin(gpio_name: &str) -> Result<String> {
-    let (port, pin) = crate::pin2tuple::parse_gpio_name(gpio_name)
+/// Renders `board_init()`, initializing LEDs and buttons and executing the target's quirks.
+fn render_board_init(target: &Target, pins: ChipPins) -> Result<String> {
+    let mut s = String::new();
+
+    s.push_str("\nvoid board_init(void)\n{\n");
+
+    if target.has_leds() {
+        s.push_str("    /* initialize the on-board LEDs */\n");
+        for n in 0..target.leds.len() {
+            let _ = writeln!(s, "    gpio_init(LED{n}_PIN, GPIO_OUT);");
+            let _ = writeln!(s, "    LED{n}_OFF;");
+        }
+    }
+
+    if target.has_buttons() {
+        s.push_str("    /* initialize the on-board buttons */\n");
+        for n in 0..target.buttons.len() {
+            let _ = writeln!(s, "    gpio_init(BTN{n}_PIN, BTN{n}_MODE);");
+        }
+    }
+
+    for quirk in &target.quirks {
+        match quirk {
+            Quirk::SetPin(set_pin_op) => {
+                if let Some(description) = &set_pin_op.description {
+                    let _ = writeln!(s, "    /* {description} */");
+                }
+                let pin = name2riot_pin(pins, &set_pin_op.pin)?;
+                let _ = writeln!(s, "    gpio_init({pin}, GPIO_OUT);");
+                let _ = match set_pin_op.level {
+                    PinLevel::High => writeln!(s, "    gpio_set({pin});"),
+                    PinLevel::Low => writeln!(s, "    gpio_clear({pin});"),
+                };
+            }
+        }
+    }
+
+    s.push_str("}\n");
+
+    Ok(s)
+}
+
+/// Renders the `LED<n>_PIN` and `LED<n>_ON`/`LED<n>_OFF`/`LED<n>_TOGGLE` defines for `board.h`.
+fn render_leds<'a>(
+    target: &'a Target,
+    pins: ChipPins,
+    resources: &mut Resources<'a>,
+) -> Result<String> {
+    let mut s = String::new();
+
+    s.push_str("/**\n * @name    LED pin configuration\n * @{\n */\n");
+    for (n, led) in target.leds.iter().enumerate() {
+        resources.claim(&led.pin, format!("LED{n}"))?;
+
+        let inverted = led.active == Some(PinActive::Low);
+        let (on, off) = if inverted {
+            ("gpio_clear", "gpio_set")
+        } else {
+            ("gpio_set", "gpio_clear")
+        };
+        let _ = writeln!(
+            s,
+            "#define LED{n}_PIN          {}",
+            name2riot_pin(pins, &led.pin)?
+        );
+        let _ = writeln!(
+            s,
+            "#define LED{n}_IS_INVERTED          {}",
+            u8::from(inverted)
+        );
+        let _ = writeln!(s, "#define LED{n}_ON          {on}(LED{n}_PIN)");
+        let _ = writeln!(s, "#define LED{n}_OFF          {off}(LED{n}_PIN)");
+        let _ = writeln!(s, "#define LED{n}_TOGGLE          gpio_toggle(LED{n}_PIN)");
+    }
+    s.push_str("/** @} */\n\n");
+
+    Ok(s)
+}
+
+/// Renders the `BTN<n>_PIN` and `BTN<n>_MODE` defines for `board.h`.
+fn render_buttons<'a>(
+    target: &'a Target,
+    pins: ChipPins,
+    resources: &mut Resources<'a>,
+) -> Result<String> {
+    let mut s = String::new();
+
+    s.push_str("/**\n * @name    Button pin configuration\n * @{\n */\n");
+    for (n, button) in target.buttons.iter().enumerate() {
+        resources.claim(&button.pin, format!("BTN{n}"))?;
+
+        let mode = match button.effective_pull() {
+            Pull::Up => "GPIO_IN_PU",
+            Pull::Down => "GPIO_IN_PD",
+            Pull::None => "GPIO_IN",
+        };
+        let _ = writeln!(
+            s,
+            "#define BTN{n}_PIN          {}",
+            name2riot_pin(pins, &button.pin)?
+        );
+        let _ = writeln!(s, "#define BTN{n}_MODE          {mode}");
+
+        for alias in &button.aliases {
+            let alias = c_macro_prefix(alias, "BTN");
+            let _ = writeln!(s, "#define {alias}_PIN          BTN{n}_PIN");
+            let _ = writeln!(s, "#define {alias}_MODE          BTN{n}_MODE");
+        }
+    }
+    s.push_str("/** @} */\n\n");
+
+    Ok(s)
+}
+
+/// Renders the `<TYPE>_PARAM_*` defines picked up by the device driver's `*_params.h`.
+fn render_device_params(
+    resolved: &ResolvedDevice,
+    pins: ChipPins,
+    i2c_devs: &[Option<usize>],
+    spi_devs: &[Option<usize>],
+) -> Result<String> {
+    let device = resolved.device;
+    let prefix = c_macro_prefix(&device.type_, "DEV");
+    let mut s = String::new();
+
+    let _ = writeln!(
+        s,
+        "/**\n * @name    {} ({})\n * @{{\n */",
+        device.name, device.type_
+    );
+
+    match resolved.bus {
+        Some(BusRef::I2c(n, _)) => {
+            let dev = i2c_devs[n]
+                .ok_or_else(|| anyhow!("device `{}`: bus I2C{n} is unavailable", device.name))?;
+            let _ = writeln!(s, "#define {prefix}_PARAM_I2C          I2C_DEV({dev})");
+            if let Some(address) = device.address {
+                let _ = writeln!(s, "#define {prefix}_PARAM_ADDR          ({address:#04x})");
+            }
+        }
+        Some(BusRef::Spi(n, _)) => {
+            let dev = spi_devs[n]
+                .ok_or_else(|| anyhow!("device `{}`: bus SPI{n} is unavailable", device.name))?;
+            let _ = writeln!(s, "#define {prefix}_PARAM_SPI          SPI_DEV({dev})");
+        }
+        None => {}
+    }
+    if let Some(cs_pin) = resolved.cs_pin {
+        let _ = writeln!(
+            s,
+            "#define {prefix}_PARAM_CS          {}",
+            name2riot_pin(pins, cs_pin)?
+        );
+    }
+    for (n, int_pin) in device.int_pins.iter().enumerate() {
+        let _ = writeln!(
+            s,
+            "#define {prefix}_PARAM_INT{}          {}",
+            n + 1,
+            name2riot_pin(pins, int_pin)?
+        );
+    }
+    if let Some(ref enable_pin) = device.enable_pin {
+        let _ = writeln!(
+            s,
+            "#define {prefix}_PARAM_EN_PIN          {}",
+            name2riot_pin(pins, enable_pin)?
+        );
+    }
+    for (key, value) in &device.properties {
+        let _ = writeln!(
+            s,
+            "#define {prefix}_PARAM_{}          {}",
+            c_name(key),
+            c_value(value)
+        );
+    }
+
+    s.push_str("/** @} */\n\n");
+
+    Ok(s)
+}
+
+/// Turns `name` into a part of a C macro name, consisting of `[A-Z0-9_]`.
+fn c_name(name: &str) -> String {
+    ident_part(name).to_ascii_uppercase()
+}
+
+/// Turns `name` into the start of a C macro name, putting `prefix` in front of names that would
+/// start with a digit.
+///
+/// A leading `_` would not do, as names starting with `_` and an uppercase letter are reserved.
+fn c_macro_prefix(name: &str, prefix: &str) -> String {
+    let name = c_name(name);
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("{prefix}_{name}")
+    } else {
+        name
+    }
+}
+
+/// Renders a property value as C expression.
+///
+/// Strings that are C identifiers (e.g., a driver's enum constant) are used as they are, all
+/// other strings become string literals.
+fn c_value(value: &Scalar) -> String {
+    match value {
+        Scalar::Bool(_) | Scalar::Integer(_) => format!("({value})"),
+        Scalar::String(s)
+            if s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            s.clone()
+        }
+        Scalar::String(s) => {
+            let mut literal = String::from("\"");
+            for c in s.chars() {
+                match c {
+                    '"' | '\\' => {
+                        literal.push('\\');
+                        literal.push(c);
+                    }
+                    '\n' => literal.push_str("\\n"),
+                    c if c.is_ascii_control() => {
+                        let _ = write!(literal, "\\{:03o}", u32::from(c));
+                    }
+                    c => literal.push(c),
+                }
+            }
+            literal.push('"');
+            literal
+        }
+    }
+}
+
+/// Lists the peripherals RIOT has configurations for on a chip.
+fn os_peripherals(peripherals: &RiotChipPeripherals) -> OsPeripherals<'_> {
+    let uarts = peripherals.uarts.keys().map(|k| (PeripheralKind::Uart, k));
+    let i2cs = peripherals.i2cs.keys().map(|k| (PeripheralKind::I2c, k));
+    let spis = peripherals.spis.keys().map(|k| (PeripheralKind::Spi, k));
+
+    OsPeripherals {
+        os: "RIOT",
+        peripherals: uarts
+            .chain(i2cs)
+            .chain(spis)
+            .map(|(kind, name)| (kind, name.as_str()))
+            .collect(),
+    }
+}
+
+/// Returns the configuration of the peripheral assigned to `bus`.
+fn assigned_peripheral<'a, T>(
+    resources: &Resources,
+    bus: &str,
+    peripherals: &'a BTreeMap<String, T>,
+) -> Option<&'a T> {
+    resources
+        .peripheral(bus)
+        .and_then(|peripheral| peripherals.get(peripheral))
+}
+
+/// Renders the `<kind>_config[]` array, ISR defines and `<KIND>_NUMOF` for `periph_conf.h`.
+fn render_periph_config(
+    kind: &str,
+    configured: &[(BTreeMap<String, String>, Option<String>)],
+) -> String {
+    let kind_upper = kind.to_uppercase();
+    let mut s = String::new();
+
+    s.push('\n');
+
+    // generate cfg struct
+    {
+        let _ = writeln!(s, "static const {kind}_conf_t {kind}_config[] = {{");
+        for (cfg, _) in configured {
+            s.push_str("    {\n");
+            for (k, v) in cfg {
+                let _ = writeln!(s, "        .{k} = {v},");
+            }
+            s.push_str("    },\n");
+        }
+        s.push_str("};\n\n");
+    }
+
+    for (n, (_, isr)) in configured.iter().enumerate() {
+        if let Some(isr) = isr {
+            let _ = writeln!(s, "#define {kind_upper}_{n}_ISR          ({isr})");
+        }
+    }
+
+    let _ = writeln!(
+        s,
+        "#define {kind_upper}_NUMOF          ARRAY_SIZE({kind}_config)\n"
+    );
+
+    s
+}
+
+/// Converts an optional pin name, using `GPIO_UNDEF` for unconnected pins.
+fn optional_riot_pin(pins: ChipPins, gpio_name: Option<&str>) -> Result<String> {
+    gpio_name.map_or_else(|| Ok("GPIO_UNDEF".into()), |name| name2riot_pin(pins, name))
+}
+
+/// RIOT's `i2c_speed_t` values, with their frequencies (in Hz).
+const RIOT_I2C_SPEEDS: [(u32, &str); 5] = [
+    (10_000, "I2C_SPEED_LOW"),
+    (100_000, "I2C_SPEED_NORMAL"),
+    (400_000, "I2C_SPEED_FAST"),
+    (1_000_000, "I2C_SPEED_FAST_PLUS"),
+    (3_400_000, "I2C_SPEED_HIGH"),
+];
+
+/// Maps an I2C bus frequency (in Hz) to RIOT's `i2c_speed_t`, returning it with its frequency.
+///
+/// Frequencies between the supported speeds are rounded down, so that devices on the bus are not
+/// clocked faster than specified.
+fn riot_i2c_speed(frequency: u32) -> Result<(&'static str, u32)> {
+    RIOT_I2C_SPEEDS
+        .iter()
+        .rev()
+        .find(|(speed_frequency, _)| *speed_frequency <= frequency)
+        .map(|(speed_frequency, speed)| (*speed, *speed_frequency))
+        .ok_or_else(|| {
+            anyhow!("I2C frequency {frequency} Hz is below RIOT's lowest `i2c_speed_t` (10 kHz)")
+        })
+}
+
+fn name2riot_pin(pins: ChipPins, gpio_name: &str) -> Result<String> {
+    let (port, pin) = pins
+        .parse(gpio_name)
         .ok_or_else(|| anyhow!("error parsing GPIO name: {gpio_name}"))?;
 
     Ok(format!("GPIO_PIN({port}, {pin})"))
 }
+
+#[test]
+fn test_render_leds() {
+    use sbd_gen_schema::Led;
+
+    let target = Target {
+        leds: vec![
+            Led {
+                pin: "P0_13".to_owned(),
+                color: None,
+                active: Some(PinActive::Low),
+                aliases: vec![],
+            },
+            Led {
+                pin: "P1_02".to_owned(),
+                color: None,
+                active: None,
+                aliases: vec![],
+            },
+        ],
+        ..crate::ariel::test_default_target()
+    };
+
+    let mut resources = Resources::new(&target, None, None);
+    let rendered = render_leds(&target, ChipPins::default(), &mut resources).unwrap();
+    assert_eq!(
+        rendered,
+        "/**
+ * @name    LED pin configuration
+ * @{
+ */
+#define LED0_PIN          GPIO_PIN(0, 13)
+#define LED0_IS_INVERTED          1
+#define LED0_ON          gpio_clear(LED0_PIN)
+#define LED0_OFF          gpio_set(LED0_PIN)
+#define LED0_TOGGLE          gpio_toggle(LED0_PIN)
+#define LED1_PIN          GPIO_PIN(1, 2)
+#define LED1_IS_INVERTED          0
+#define LED1_ON          gpio_set(LED1_PIN)
+#define LED1_OFF          gpio_clear(LED1_PIN)
+#define LED1_TOGGLE          gpio_toggle(LED1_PIN)
+/** @} */
+
+"
+    );
+}
+
+#[test]
+fn test_render_buttons() {
+    use sbd_gen_schema::Button;
+
+    let target = Target {
+        buttons: vec![Button {
+            pin: "P0_11".to_owned(),
+            active: Some(PinActive::Low),
+            aliases: vec!["user-btn".to_owned(), "2nd.key".to_owned()],
+            pull: None,
+            debounce_ms: None,
+        }],
+        ..crate::ariel::test_default_target()
+    };
+
+    let mut resources = Resources::new(&target, None, None);
+    let rendered = render_buttons(&target, ChipPins::default(), &mut resources).unwrap();
+    assert_eq!(
+        rendered,
+        "/**
+ * @name    Button pin configuration
+ * @{
+ */
+#define BTN0_PIN          GPIO_PIN(0, 11)
+#define BTN0_MODE          GPIO_IN_PU
+#define USER_BTN_PIN          BTN0_PIN
+#define USER_BTN_MODE          BTN0_MODE
+#define BTN_2ND_KEY_PIN          BTN0_PIN
+#define BTN_2ND_KEY_MODE          BTN0_MODE
+/** @} */
+
+"
+    );
+}
+
+#[test]
+fn test_render_board_init() {
+    use sbd_gen_schema::{Button, SetPinOp};
+
+    let target = Target {
+        buttons: vec![Button {
+            pin: "P0_11".to_owned(),
+            active: Some(PinActive::Low),
+            aliases: vec![],
+            pull: None,
+            debounce_ms: None,
+        }],
+        quirks: vec![Quirk::SetPin(SetPinOp {
+            description: Some("enable power".to_owned()),
+            pin: "P0_31".to_owned(),
+            level: PinLevel::High,
+        })],
+        ..crate::ariel::test_default_target()
+    };
+
+    let rendered = render_board_init(&target, ChipPins::default()).unwrap();
+    assert_eq!(
+        rendered,
+        "
+void board_init(void)
+{
+    /* initialize the on-board buttons */
+    gpio_init(BTN0_PIN, BTN0_MODE);
+    /* enable power */
+    gpio_init(GPIO_PIN(0, 31), GPIO_OUT);
+    gpio_set(GPIO_PIN(0, 31));
+}
+"
+    );
+}
+
+#[test]
+fn test_riot_i2c_speed() {
+    assert_eq!(
+        riot_i2c_speed(400_000).unwrap(),
+        ("I2C_SPEED_FAST", 400_000)
+    );
+    assert_eq!(
+        riot_i2c_speed(200_000).unwrap(),
+        ("I2C_SPEED_NORMAL", 100_000)
+    );
+    assert_eq!(
+        riot_i2c_speed(1_700_000).unwrap(),
+        ("I2C_SPEED_FAST_PLUS", 1_000_000)
+    );
+    assert!(riot_i2c_speed(5_000).is_err());
+}
+
+#[test]
+fn test_render_device_params() {
+    use sbd_gen_schema::Device;
+
+    let device = Device {
+        name: "flash".to_owned(),
+        type_: "3d.mem/x".to_owned(),
+        class: DeviceClass::Flash,
+        description: None,
+        bus: None,
+        address: None,
+        cs_pin: None,
+        int_pins: vec![],
+        enable_pin: None,
+        properties: BTreeMap::from([
+            ("page-size".to_owned(), Scalar::Integer(256)),
+            (
+                "mode".to_owned(),
+                Scalar::String("MEM_MODE_FAST".to_owned()),
+            ),
+            (
+                "label.name".to_owned(),
+                Scalar::String("ext \"flash\"".to_owned()),
+            ),
+        ]),
+    };
+    let resolved = ResolvedDevice {
+        device: &device,
+        bus: None,
+        cs_pin: None,
+    };
+
+    let rendered = render_device_params(&resolved, ChipPins::default(), &[], &[]).unwrap();
+    assert_eq!(
+        rendered,
+        "/**
+ * @name    flash (3d.mem/x)
+ * @{
+ */
+#define DEV_3D_MEM_X_PARAM_LABEL_NAME          \"ext \\\"flash\\\"\"
+#define DEV_3D_MEM_X_PARAM_MODE          MEM_MODE_FAST
+#define DEV_3D_MEM_X_PARAM_PAGE_SIZE          (256)
+/** @} */
+
+"
+    );
+}
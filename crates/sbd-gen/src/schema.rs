@@ -0,0 +1,90 @@
+//! JSON Schema generation
+//!
+//! The schema lets editors (e.g., through yaml-language-server) complete and validate SBD files
+//! while they are being written.
+//!
+//! Target fragment files (see `Target::include`) only contain target fields, so they need their
+//! own schema, printed with `--fragment`. With yaml-language-server, map each schema to its files
+//! through the `yaml.schemas` setting, e.g.,
+//! `{"sbd.schema.json": "boards/*.yaml", "sbd-fragment.schema.json": "boards/fragments/*.yaml"}`,
+//! or name the schema in a `# yaml-language-server: $schema=<path>` comment at the top of a file.
+
+use anyhow::Result;
+use camino::Utf8PathBuf;
+
+#[derive(argh::FromArgs, Debug)]
+#[argh(subcommand, name = "schema")]
+/// print the JSON Schema of SBD files
+pub struct SchemaArgs {
+    /// output file (default: stdout)
+    #[argh(option, short = 'o')]
+    output: Option<Utf8PathBuf>,
+
+    /// print the schema of target fragment files instead
+    #[argh(switch)]
+    fragment: bool,
+}
+
+pub fn schema(args: &SchemaArgs) -> Result<()> {
+    let schema = if args.fragment {
+        sbd_gen_schema::json_schema_fragment()
+    } else {
+        sbd_gen_schema::json_schema()
+    };
+    let mut output = serde_json::to_string_pretty(&schema)?;
+    output.push('\n');
+
+    match args.output {
+        Some(ref path) => std::fs::write(path, output)?,
+        None => print!("{output}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_json_schema() {
+        let schema = sbd_gen_schema::json_schema();
+
+        assert_eq!(
+            schema.get("title").and_then(|title| title.as_str()),
+            Some(
+                format!(
+                    "SBD file (schema version {})",
+                    sbd_gen_schema::schema_version()
+                )
+                .as_str()
+            )
+        );
+
+        // Targets are keyed by name, without a `$key$` property.
+        let target = schema
+            .pointer("/$defs/KeyValueMap(Target)/additionalProperties/properties")
+            .unwrap();
+        assert!(target.get("chip").is_some());
+        assert!(target.get("$key$").is_none());
+    }
+
+    #[test]
+    fn test_json_schema_fragment() {
+        let schema = sbd_gen_schema::json_schema_fragment();
+        let properties = schema.get("properties").unwrap();
+
+        // Every field of a fragment is known to the fragment schema, and none is required.
+        let fragment: serde_yaml::Mapping = serde_yaml::from_str(
+            &std::fs::read_to_string("sbd-test-files/fragments/arduino-header.yaml").unwrap(),
+        )
+        .unwrap();
+        for key in fragment.keys() {
+            assert!(properties.get(key.as_str().unwrap()).is_some(), "{key:?}");
+        }
+        assert!(properties.get("version").is_some());
+        assert!(properties.get("chip").is_none());
+        assert!(schema.get("required").is_none());
+
+        // References into the definitions still resolve.
+        assert!(schema.pointer("/$defs/I2c").is_some());
+    }
+}
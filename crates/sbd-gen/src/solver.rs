@@ -0,0 +1,312 @@
+//! Assignment of peripherals to buses
+//!
+//! Every bus (UART, I2C, SPI) of a target lists the peripherals that could serve it. This picks
+//! one peripheral per bus, such that no peripheral is used by two buses and all of a bus' pins
+//! can be routed to its peripheral. Buses are assigned one after the other; when a bus' candidates
+//! are all taken, earlier choices are revisited (backtracking along augmenting paths).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use sbd_gen_schema::chip::{Chip, PeripheralKind};
+
+use crate::chips::Bus;
+
+/// Peripherals an OS has configurations for.
+///
+/// When given, buses are only assigned peripherals in this list. Buses that do not list any
+/// possible peripherals may use any of them.
+#[derive(Debug)]
+pub struct OsPeripherals<'a> {
+    /// OS name, for messages.
+    pub os: &'a str,
+    pub peripherals: Vec<(PeripheralKind, &'a str)>,
+}
+
+impl OsPeripherals<'_> {
+    fn of_kind(&self, kind: PeripheralKind) -> impl Iterator<Item = &str> {
+        self.peripherals
+            .iter()
+            .filter(move |(k, _)| *k == kind)
+            .map(|(_, name)| *name)
+    }
+
+    fn contains(&self, kind: PeripheralKind, name: &str) -> bool {
+        self.of_kind(kind).any(|p| p == name)
+    }
+}
+
+/// The peripherals a bus can use.
+#[derive(Debug)]
+pub struct Candidates {
+    pub bus: String,
+    /// Usable peripherals, in order of preference.
+    pub peripherals: Vec<String>,
+    /// Reasons why possible peripherals of the bus cannot be used.
+    pub excluded: Vec<String>,
+}
+
+/// Determines the peripherals a bus can use.
+pub fn candidates(bus: &Bus, chip: Option<&Chip>, os: Option<&OsPeripherals>) -> Candidates {
+    let mut candidates = Candidates {
+        bus: bus.name.clone(),
+        peripherals: Vec::new(),
+        excluded: Vec::new(),
+    };
+
+    let possible: Vec<&str> = if bus.possible_peripherals.is_empty() {
+        // Without any restrictions from the board, the OS is free to choose.
+        os.map(|os| os.of_kind(bus.kind).collect())
+            .unwrap_or_default()
+    } else {
+        bus.possible_peripherals
+            .iter()
+            .map(String::as_str)
+            .collect()
+    };
+
+    for peripheral in possible {
+        if let Some(os) = os
+            && !os.contains(bus.kind, peripheral)
+        {
+            candidates.excluded.push(format!(
+                "{} has no {} configuration for {peripheral}",
+                os.os,
+                bus.kind.as_str()
+            ));
+            continue;
+        }
+
+        if let Some(chip) = chip {
+            let Some(chip_peripheral) = chip.peripheral(peripheral) else {
+                candidates
+                    .excluded
+                    .push(format!("{peripheral} does not exist on {}", chip.name));
+                continue;
+            };
+            if chip_peripheral.kind != bus.kind {
+                candidates
+                    .excluded
+                    .push(format!("{peripheral} is not of kind {}", bus.kind.as_str()));
+                continue;
+            }
+            if let Some((signal, pin)) = bus
+                .signals
+                .iter()
+                .find(|(signal, pin)| chip_peripheral.route(signal, pin).is_none())
+            {
+                candidates.excluded.push(format!(
+                    "{signal} pin {pin} cannot be routed to {peripheral}"
+                ));
+                continue;
+            }
+        }
+
+        candidates.peripherals.push(peripheral.to_string());
+    }
+
+    candidates
+}
+
+/// The result of [`solve()`].
+#[derive(Debug, Default)]
+pub struct Assignment {
+    /// Assigned peripherals, keyed by bus name.
+    pub peripherals: BTreeMap<String, String>,
+    /// Why buses could not be assigned a peripheral.
+    pub problems: Vec<String>,
+}
+
+/// Assigns a peripheral to each bus.
+///
+/// Buses without any candidates (and without excluded ones, i.e., buses that do not name any
+/// peripherals) stay unassigned. So do buses that cannot be assigned a peripheral, which is
+/// explained in [`Assignment::problems`]; the other buses are still assigned.
+pub fn solve(candidates: &[Candidates]) -> Assignment {
+    // maps a peripheral to the index of the bus it is assigned to
+    let mut owners = BTreeMap::new();
+    let mut problems = Vec::new();
+
+    for (n, bus) in candidates.iter().enumerate() {
+        if bus.peripherals.is_empty() {
+            if !bus.excluded.is_empty() {
+                problems.push(format!(
+                    "cannot assign a peripheral to {}: none of its possible peripherals can be used\n{}",
+                    bus.bus,
+                    explain(candidates, &[n])
+                ));
+            }
+            continue;
+        }
+
+        let mut visited = BTreeSet::new();
+        if !assign(candidates, n, &mut owners, &mut visited) {
+            // All visited peripherals are taken by buses that could not be moved elsewhere, so
+            // together with this bus, those buses compete for too few peripherals.
+            let mut buses = visited.iter().map(|p| owners[p]).collect::<Vec<_>>();
+            buses.push(n);
+            buses.sort_unstable();
+
+            let names = buses
+                .iter()
+                .map(|&n| candidates[n].bus.as_str())
+                .collect::<Vec<_>>();
+            problems.push(format!(
+                "cannot assign a peripheral to {}: {} need {} peripherals, but can only use {}\n{}",
+                bus.bus,
+                names.join(", "),
+                buses.len(),
+                visited.into_iter().collect::<Vec<_>>().join(", "),
+                explain(candidates, &buses)
+            ));
+        }
+    }
+
+    Assignment {
+        peripherals: owners
+            .into_iter()
+            .map(|(peripheral, n)| (candidates[n].bus.clone(), peripheral.to_string()))
+            .collect(),
+        problems,
+    }
+}
+
+/// Tries to assign a peripheral to bus `n`, re-assigning other buses if needed.
+///
+/// Free peripherals are preferred, so that buses keep their earlier (preferred) choices where
+/// possible.
+fn assign<'a>(
+    candidates: &'a [Candidates],
+    n: usize,
+    owners: &mut BTreeMap<&'a str, usize>,
+    visited: &mut BTreeSet<&'a str>,
+) -> bool {
+    if let Some(peripheral) = candidates[n]
+        .peripherals
+        .iter()
+        .find(|p| !owners.contains_key(p.as_str()))
+    {
+        visited.insert(peripheral);
+        owners.insert(peripheral, n);
+        return true;
+    }
+
+    for peripheral in &candidates[n].peripherals {
+        if !visited.insert(peripheral) {
+            continue;
+        }
+
+        let available = match owners.get(peripheral.as_str()) {
+            None => true,
+            Some(&other) => assign(candidates, other, owners, visited),
+        };
+        if available {
+            owners.insert(peripheral, n);
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Lists the candidates of the given buses, and why other peripherals are excluded.
+fn explain(candidates: &[Candidates], buses: &[usize]) -> String {
+    let mut lines = Vec::new();
+    for &n in buses {
+        let bus = &candidates[n];
+        if bus.peripherals.is_empty() {
+            lines.push(format!("  {}: no usable peripherals", bus.bus));
+        } else {
+            lines.push(format!(
+                "  {}: can use {}",
+                bus.bus,
+                bus.peripherals.join(", ")
+            ));
+        }
+        for reason in &bus.excluded {
+            lines.push(format!("  {}: {reason}", bus.bus));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus(name: &str, peripherals: &[&str]) -> Candidates {
+        Candidates {
+            bus: name.to_string(),
+            peripherals: peripherals.iter().map(ToString::to_string).collect(),
+            excluded: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_solve() {
+        // uart0 prefers UART0, but needs to make way for uart1.
+        let assignment = solve(&[
+            bus("uart0", &["UART0", "UART1"]),
+            bus("uart1", &["UART0"]),
+            bus("uart2", &[]),
+        ])
+        .peripherals;
+
+        assert_eq!(
+            assignment.into_iter().collect::<Vec<_>>(),
+            [
+                ("uart0".to_string(), "UART1".to_string()),
+                ("uart1".to_string(), "UART0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_solve_prefers_free_peripherals() {
+        // uart1 takes its free second choice rather than taking UART0 from uart0.
+        let assignment = solve(&[
+            bus("uart0", &["UART0", "UART1"]),
+            bus("uart1", &["UART0", "UART2"]),
+        ])
+        .peripherals;
+
+        assert_eq!(
+            assignment.into_iter().collect::<Vec<_>>(),
+            [
+                ("uart0".to_string(), "UART0".to_string()),
+                ("uart1".to_string(), "UART2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_solve_unsatisfiable() {
+        let mut spi0 = bus("spi0", &["SPI0"]);
+        spi0.excluded
+            .push("sck pin P0_01 cannot be routed to SPI1".to_string());
+
+        let assignment = solve(&[
+            bus("i2c0", &["SPI0", "TWI1"]),
+            bus("uart0", &["UART0"]),
+            bus("i2c1", &["TWI1"]),
+            spi0,
+        ]);
+
+        // The other buses are still assigned.
+        assert_eq!(
+            assignment.peripherals.into_iter().collect::<Vec<_>>(),
+            [
+                ("i2c0".to_string(), "SPI0".to_string()),
+                ("i2c1".to_string(), "TWI1".to_string()),
+                ("uart0".to_string(), "UART0".to_string()),
+            ]
+        );
+        assert_eq!(
+            assignment.problems,
+            ["cannot assign a peripheral to spi0: i2c0, i2c1, spi0 need 3 peripherals, but can only use SPI0, TWI1
+  i2c0: can use SPI0, TWI1
+  i2c1: can use TWI1
+  spi0: can use SPI0
+  spi0: sck pin P0_01 cannot be routed to SPI1"]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! SBD file validation
+//!
+//! Every file is checked on its own, so errors can be reported with their precise location.
+//! Deserialization stops at the first schema error, so at most one is reported per file (besides
+//! a version error); fixing it may reveal the next one.
+
+use std::fmt::Write as _;
+
+use anyhow::{Result, bail};
+
+use sbd_gen_schema::{SbdFile, SbdFileVersion};
+
+use crate::{
+    collect_sbd_files, conflicts::ConflictPolicy, include::IncludedFiles, parse_sbd_files,
+};
+
+#[derive(argh::FromArgs, Debug)]
+#[argh(subcommand, name = "validate")]
+/// validate SBD files, reporting the first schema error of each file
+pub struct ValidateArgs {
+    /// the name of the directory containing board descriptions
+    #[argh(positional)]
+    sbd_dir: String,
+}
+
+/// An error found in a file.
+#[derive(Debug)]
+pub(crate) struct Diagnostic {
+    file: String,
+    /// 1-based line and column.
+    location: Option<(usize, usize)>,
+    message: String,
+}
+
+impl Diagnostic {
+    fn from_yaml_error(
+        file: &str,
+        error: &serde_yaml::Error,
+        line_offset: usize,
+        column_offset: usize,
+    ) -> Self {
+        // The error message repeats the location, which is rendered separately here.
+        let message = error.to_string();
+        let message = lazy_regex::regex_replace!(r" at line \d+ column \d+$", &message, "");
+
+        Self {
+            file: file.to_string(),
+            location: error.location().map(|l| {
+                (
+                    l.line().saturating_sub(line_offset),
+                    l.column().saturating_sub(column_offset),
+                )
+            }),
+            message: message.into_owned(),
+        }
+    }
+
+    /// Renders the diagnostic, including a snippet of the offending line.
+    fn render(&self, content: &str) -> String {
+        let mut s = String::new();
+
+        let Some((line, column)) = self.location else {
+            let _ = writeln!(s, "error: {}: {}", self.file, self.message);
+            return s;
+        };
+
+        let _ = writeln!(s, "error: {}:{line}:{column}: {}", self.file, self.message);
+        if let Some(source_line) = content.lines().nth(line.saturating_sub(1)) {
+            let width = line.to_string().len();
+            let _ = writeln!(s, "{:width$} |", "");
+            let _ = writeln!(s, "{line} | {source_line}");
+            let _ = writeln!(s, "{:width$} | {:>column$}", "", "^");
+        }
+
+        s
+    }
+}
+
+pub fn validate(args: &ValidateArgs, conflicts: ConflictPolicy) -> Result<()> {
+    let files = collect_sbd_files(&args.sbd_dir);
+
+    let mut errors = 0;
+    let report = |errors: &mut usize, diagnostic: &Diagnostic, content: &str| {
+        eprint!("{}", diagnostic.render(content));
+        *errors += 1;
+    };
+
+    let included = IncludedFiles::scan(&files).unwrap_or_else(|e| {
+        report(
+            &mut errors,
+            &Diagnostic {
+                file: args.sbd_dir.clone(),
+                location: None,
+                message: format!("{e:#}"),
+            },
+            "",
+        );
+        IncludedFiles::default()
+    });
+
+    for file in &files {
+        let content = match std::fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(e) => {
+                let diagnostic = Diagnostic {
+                    file: file.clone(),
+                    location: None,
+                    message: e.to_string(),
+                };
+                report(&mut errors, &diagnostic, "");
+                continue;
+            }
+        };
+
+        let is_fragment = included.is_fragment(file).unwrap_or(false);
+        eprintln!("sbd: validating '{file}'");
+
+        for diagnostic in validate_file(file, &content, is_fragment) {
+            report(&mut errors, &diagnostic, &content);
+        }
+    }
+
+    // Only if all files are fine on their own, check them as a whole (includes, inheritance,
+    // ...).
+    if errors == 0
+        && let Err(e) = parse_sbd_files(&args.sbd_dir, conflicts)
+    {
+        report(
+            &mut errors,
+            &Diagnostic {
+                file: args.sbd_dir.clone(),
+                location: None,
+                message: format!("{e:#}"),
+            },
+            "",
+        );
+    }
+
+    if errors > 0 {
+        bail!("validation failed with {errors} error(s)");
+    }
+
+    println!("sbd: all files are valid");
+
+    Ok(())
+}
+
+/// Validates a single file's content.
+pub(crate) fn validate_file(file: &str, content: &str, is_fragment: bool) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    match serde_yaml::from_str::<SbdFileVersion>(content) {
+        // Fragments without a version are fine, the including file's version applies.
+        Ok(_) if is_fragment && find_key(content, "version").is_none() => {}
+        Ok(version) if !version.is_compatible() => diagnostics.push(Diagnostic {
+            file: file.to_string(),
+            location: find_key(content, "version"),
+            message: format!(
+                "version {} is not compatible with the used schema version {}{}",
+                version.version,
+                sbd_gen_schema::schema_version(),
+                if version.version < sbd_gen_schema::schema_version() {
+                    " (try `sbd-gen migrate`)"
+                } else {
+                    ""
+                }
+            ),
+        }),
+        Ok(_) => {}
+        Err(e) => {
+            diagnostics.push(Diagnostic::from_yaml_error(file, &e, 0, 0));
+            // Without valid YAML, there is nothing more to check.
+            return diagnostics;
+        }
+    }
+
+    let result = if is_fragment {
+        let (wrapped, line_offset, column_offset) = wrap_fragment(content);
+        serde_yaml::from_str::<SbdFile>(&wrapped).err().map(|e| {
+            let mut diagnostic = Diagnostic::from_yaml_error(file, &e, line_offset, column_offset);
+            diagnostic.message = diagnostic.message.replacen("targets.fragment.", "", 1);
+            diagnostic
+        })
+    } else {
+        serde_yaml::from_str::<SbdFile>(content)
+            .err()
+            .map(|e| Diagnostic::from_yaml_error(file, &e, 0, 0))
+    };
+    diagnostics.extend(result);
+
+    diagnostics
+}
+
+/// Wraps a target fragment into an SBD file with a single target, so it can be checked against
+/// the schema.
+///
+/// Returns the wrapped content and the line and column offsets of the fragment within.
+fn wrap_fragment(content: &str) -> (String, usize, usize) {
+    const INDENT: &str = "    ";
+
+    let mut wrapped = String::from("targets:\n  fragment:\n");
+    let mut line_offset = 2;
+
+    // A fragment need not name a chip.
+    if find_key(content, "chip").is_none() {
+        let _ = writeln!(wrapped, "{INDENT}chip: \"\"");
+        line_offset += 1;
+    }
+
+    for line in content.lines() {
+        // The version is not a target field, but was checked already.
+        if line.starts_with("version:") {
+            let _ = writeln!(wrapped, "{INDENT}# {line}");
+        } else {
+            let _ = writeln!(wrapped, "{INDENT}{line}");
+        }
+    }
+
+    (wrapped, line_offset, INDENT.len())
+}
+
+/// Finds the location of a top-level key.
+fn find_key(content: &str, key: &str) -> Option<(usize, usize)> {
+    content
+        .lines()
+        .position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+        .map(|n| (n + 1, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_file() {
+        let content = "version: 0.4.0\ntargets:\n  a:\n    chip: x\n    bogus: 1\n";
+        let diagnostics = validate_file("a.yaml", content, false);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some((5, 5)));
+        assert_eq!(
+            diagnostics[0].render(content).lines().collect::<Vec<_>>(),
+            [
+                "error: a.yaml:5:5: targets.a: unknown field `bogus`, expected one of `$key$`, `chip`, `description`, `include`, `extends`, `replace`, `flags`, `quirks`, `ariel`, `riot`, `debugger`, `leds`, `buttons`, `uarts`, `i2cs`, `spis`, `devices`",
+                "  |",
+                "5 |     bogus: 1",
+                "  |     ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_fragment() {
+        let content = "uarts:\n  - rx_pin: P0_01\n    tx: P0_02\n";
+        let diagnostics = validate_file("fragment.yaml", content, true);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some((3, 5)));
+        assert!(
+            diagnostics[0]
+                .message
+                .starts_with("uarts[0]: unknown field `tx`")
+        );
+
+        let content = "version: 0.1.0\nuarts: []\n";
+        let diagnostics = validate_file("fragment.yaml", content, true);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some((1, 1)));
+    }
+}
@@ -0,0 +1,997 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write as _;
+
+use anyhow::{Context as _, Result, anyhow};
+use camino::Utf8PathBuf;
+
+use crate::{
+    conflicts::ConflictPolicy,
+    filemap::{ApplyOptions, FileMap, Mode, ReportFormat, parse_mode},
+    load_sbd_files,
+    manifest::Origin,
+    pin_naming::ChipPins,
+    resources::Resources,
+    solver::OsPeripherals,
+};
+
+use sbd_gen_schema::{
+    PinActive, Pull, SbdFile, Target, Uart,
+    chip::PeripheralKind,
+    zephyr::{ZephyrChipMapEntry, ZephyrChipPeripherals, ZephyrPinctrl},
+};
+
+#[derive(argh::FromArgs, Debug)]
+#[argh(subcommand, name = "generate-zephyr")]
+/// generate Zephyr specific files
+pub struct GenerateZephyrArgs {
+    /// the name of the directory containing board descriptions
+    #[argh(positional)]
+    sbd_dir: String,
+
+    /// operation mode: create|check|update|dry-run
+    #[argh(option, short = 'm', from_str_fn(parse_mode))]
+    mode: Option<Mode>,
+
+    /// print diffs of the differing files (in check and dry-run mode)
+    #[argh(switch)]
+    diff: bool,
+
+    /// check mode result format: text|json (default: text)
+    #[argh(option, default = "ReportFormat::default()")]
+    format: ReportFormat,
+
+    /// write a junit xml report of the check mode results to this file
+    #[argh(option)]
+    junit: Option<Utf8PathBuf>,
+
+    /// overwrite files that were edited by hand (in update mode)
+    #[argh(switch)]
+    force: bool,
+
+    /// zephyr boards output dir
+    #[argh(option, short = 'o', default = "Utf8PathBuf::from(\"zephyr-boards\")")]
+    output: Utf8PathBuf,
+}
+
+pub fn generate(args: &GenerateZephyrArgs, conflicts: ConflictPolicy) -> Result<()> {
+    let (sbd_file, inputs) = load_sbd_files(args.sbd_dir.as_str(), conflicts)?;
+    let mode = args.mode.unwrap_or_default();
+
+    let origin = Origin::new::<GenerateZephyrArgs>(&args.sbd_dir, &args.output, &inputs)?;
+    let boards_dir = render_zephyr_boards_dir(&sbd_file)?.with_origin(origin);
+
+    let options = ApplyOptions {
+        diff: args.diff,
+        format: args.format,
+        junit: args.junit.clone(),
+        force: args.force,
+    };
+    mode.apply(&args.output, &boards_dir, &options)?;
+
+    Ok(())
+}
+
+pub fn render_zephyr_boards_dir(sbd: &SbdFile) -> Result<FileMap> {
+    let chips: HashSet<String> = sbd
+        .zephyr
+        .clone()
+        .unwrap_or_default()
+        .chips
+        .keys()
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    if chips.is_empty() {
+        eprintln!("warning: No supported chips defined for Zephyr");
+    }
+
+    // filter targets with unknown chips
+    let targets = sbd
+        .targets
+        .iter()
+        .flatten()
+        .filter(|target| {
+            if chips.contains(&target.chip) {
+                true
+            } else {
+                eprintln!(
+                    "warning: skipping target {}, unknown chip {}",
+                    target.name, target.chip
+                );
+                false
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if targets.is_empty() {
+        eprintln!("warning: No targets defined for Zephyr");
+    }
+
+    let mut boards_dir = FileMap::new();
+    for target in targets {
+        let board = generate_zephyr_board(sbd, target)
+            .with_context(|| anyhow!("cannot render {}", target.name))?;
+        boards_dir.extend_subdir(
+            &Utf8PathBuf::from(board_name(target)),
+            board.owned_by(&target.name),
+        );
+    }
+
+    Ok(boards_dir)
+}
+
+/// Returns the Zephyr board name of a target.
+///
+/// Zephyr board names only consist of lowercase letters, digits and underscores.
+fn board_name(target: &Target) -> String {
+    target.name.to_lowercase().replace(['-', '.'], "_")
+}
+
+fn generate_zephyr_board(sbd: &SbdFile, target: &Target) -> Result<FileMap> {
+    let mut files = FileMap::new();
+    let board = board_name(target);
+
+    // the unwrap should always succeed (filtered in caller)
+    let zephyr_chip = sbd
+        .zephyr
+        .as_ref()
+        .unwrap()
+        .chips
+        .get(&target.chip)
+        .unwrap();
+
+    let chip = sbd.chip(&target.chip);
+    let zephyr_peripherals = zephyr_chip.peripherals.clone().unwrap_or_default();
+    let mut resources = Resources::new(target, chip, Some(&os_peripherals(&zephyr_peripherals)));
+    let gpios = Gpios {
+        zephyr_chip,
+        pins: ChipPins::new(chip),
+    };
+
+    files.insert(
+        format!("{board}.dts").into(),
+        render_dts(target, &gpios, &zephyr_peripherals, &mut resources)?,
+    );
+    files.insert("board.yml".into(), render_board_yml(target, zephyr_chip));
+    files.insert(
+        format!("Kconfig.{board}").into(),
+        format!(
+            "config BOARD_{}\n\tselect {}\n",
+            board.to_uppercase(),
+            zephyr_chip.soc_kconfig
+        ),
+    );
+    files.insert(
+        format!("{board}_defconfig").into(),
+        render_defconfig(target),
+    );
+    files.insert("board.cmake".into(), render_board_cmake(target));
+
+    Ok(files)
+}
+
+fn render_board_yml(target: &Target, zephyr_chip: &ZephyrChipMapEntry) -> String {
+    let mut s = String::new();
+
+    s.push_str("board:\n");
+    let _ = writeln!(s, "  name: {}", board_name(target));
+    if let Some(ref description) = target.description {
+        let _ = writeln!(s, "  full_name: {}", quote(description));
+    }
+    s.push_str("  socs:\n");
+    let _ = writeln!(s, "    - name: {}", zephyr_chip.soc);
+
+    s
+}
+
+/// Renders `text` as double-quoted string, valid in both devicetree sources and YAML.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_ascii_control() => {
+                let _ = write!(quoted, "\\x{:02x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn render_defconfig(target: &Target) -> String {
+    let mut s = String::new();
+
+    if target.has_leds() || target.has_buttons() {
+        s.push_str("CONFIG_GPIO=y\n");
+    }
+    if host_facing_uart(target).is_some() {
+        s.push_str("CONFIG_SERIAL=y\n");
+        s.push_str("CONFIG_CONSOLE=y\n");
+        s.push_str("CONFIG_UART_CONSOLE=y\n");
+    }
+
+    s
+}
+
+fn render_board_cmake(target: &Target) -> String {
+    let mut s = String::new();
+
+    if let Some(ref debugger) = target.debugger {
+        if let Some(runner) = zephyr_runner(&debugger.type_) {
+            let _ = writeln!(
+                s,
+                "include(${{ZEPHYR_BASE}}/boards/common/{runner}.board.cmake)"
+            );
+        } else {
+            eprintln!(
+                "warning: {}: debugger type `{}` has no known Zephyr runner",
+                target.name, debugger.type_
+            );
+        }
+    }
+
+    s
+}
+
+/// Maps a debugger type to the Zephyr runner to use.
+fn zephyr_runner(debugger_type: &str) -> Option<&'static str> {
+    let runner = match debugger_type {
+        "jlink" | "j-link" => "jlink",
+        "openocd" | "stlink" | "st-link" => "openocd",
+        "probe-rs" => "probe-rs",
+        "pyocd" | "cmsis-dap" | "daplink" => "pyocd",
+        "nrfjprog" => "nrfjprog",
+        "nrfutil" => "nrfutil",
+        "blackmagicprobe" => "blackmagicprobe",
+        "linkserver" => "linkserver",
+        _ => return None,
+    };
+    Some(runner)
+}
+
+/// Lists the peripherals that have devicetree nodes on a chip.
+fn os_peripherals(peripherals: &ZephyrChipPeripherals) -> OsPeripherals<'_> {
+    let uarts = peripherals.uarts.keys().map(|k| (PeripheralKind::Uart, k));
+    let i2cs = peripherals.i2cs.keys().map(|k| (PeripheralKind::I2c, k));
+    let spis = peripherals.spis.keys().map(|k| (PeripheralKind::Spi, k));
+
+    OsPeripherals {
+        os: "Zephyr",
+        peripherals: uarts
+            .chain(i2cs)
+            .chain(spis)
+            .map(|(kind, name)| (kind, name.as_str()))
+            .collect(),
+    }
+}
+
+/// Returns the UART to use as console: the debugger's UART, or the first host facing UART.
+///
+/// The bus name is returned along with the UART.
+fn host_facing_uart(target: &Target) -> Option<(String, &Uart)> {
+    if let Some(uart) = target.debugger_uart() {
+        return Some(("debugger_uart".to_string(), uart));
+    }
+
+    target
+        .uarts
+        .iter()
+        .enumerate()
+        .find(|(_, uart)| uart.host_facing)
+        .map(|(n, uart)| (format!("uart{n}"), uart))
+}
+
+/// Converts pin names to devicetree GPIO specifiers.
+struct Gpios<'a> {
+    zephyr_chip: &'a ZephyrChipMapEntry,
+    pins: ChipPins,
+}
+
+impl Gpios<'_> {
+    /// Returns a GPIO specifier (without the surrounding `<>`), e.g., `&gpio0 13 GPIO_ACTIVE_LOW`.
+    fn spec(&self, pin_name: &str, flags: &[&str]) -> Result<String> {
+        let (port, pin) = self
+            .pins
+            .parse(pin_name)
+            .ok_or_else(|| anyhow!("error parsing GPIO name: {pin_name}"))?;
+        let controller = self
+            .zephyr_chip
+            .gpio_ports
+            .get(usize::from(port))
+            .ok_or_else(|| anyhow!("pin {pin_name}: no GPIO controller known for port {port}"))?;
+
+        let flags = match flags {
+            [] => "0".to_string(),
+            [flag] => (*flag).to_string(),
+            flags => format!("({})", flags.join(" | ")),
+        };
+
+        Ok(format!("&{controller} {pin} {flags}"))
+    }
+
+    /// Returns the pinctrl properties of the bus node `label`, assigning `signals` (e.g.,
+    /// `("tx", "P0_06")`) to pins. Pinctrl state nodes the properties refer to are added to
+    /// `nodes`.
+    ///
+    /// Returns `None` if the chip's pinctrl conventions are unknown.
+    fn pinctrl(
+        &self,
+        label: &str,
+        kind: PeripheralKind,
+        signals: &[(&str, &str)],
+        nodes: &mut String,
+    ) -> Result<Option<String>> {
+        let Some(style) = self.zephyr_chip.pinctrl else {
+            return Ok(None);
+        };
+        let pins = signals
+            .iter()
+            .map(|(signal, pin_name)| {
+                let (port, pin) = self
+                    .pins
+                    .parse(pin_name)
+                    .ok_or_else(|| anyhow!("error parsing GPIO name: {pin_name}"))?;
+                Ok((*signal, port, pin))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut s = String::new();
+        match style {
+            ZephyrPinctrl::Nrf => {
+                let function = match kind {
+                    PeripheralKind::Uart => "UART",
+                    PeripheralKind::I2c => "TWIM",
+                    PeripheralKind::Spi => "SPIM",
+                    PeripheralKind::Other => unreachable!("only buses have pinctrl"),
+                };
+                let psels = pins
+                    .iter()
+                    .map(|(signal, port, pin)| {
+                        format!(
+                            "<NRF_PSEL({function}_{}, {port}, {pin})>",
+                            signal.to_uppercase()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",\n\t\t\t\t");
+                for (state, extra) in [("default", ""), ("sleep", "\t\t\tlow-power-enable;\n")] {
+                    let _ = write!(
+                        nodes,
+                        "\n\t{label}_{state}: {label}_{state} {{\n\t\tgroup1 {{\n\t\t\tpsels = {psels};\n{extra}\t\t}};\n\t}};\n"
+                    );
+                }
+                let _ = writeln!(s, "\tpinctrl-0 = <&{label}_default>;");
+                let _ = writeln!(s, "\tpinctrl-1 = <&{label}_sleep>;");
+                s.push_str("\tpinctrl-names = \"default\", \"sleep\";\n");
+            }
+            ZephyrPinctrl::Stm32 => {
+                let pin_nodes = pins
+                    .iter()
+                    .map(|(signal, port, pin)| {
+                        let port = b'a'
+                            .checked_add(*port)
+                            .filter(u8::is_ascii_lowercase)
+                            .ok_or_else(|| anyhow!("{label}: port {port} has no STM32 name"))?;
+                        Ok(format!("&{label}_{signal}_p{}{pin}", char::from(port)))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .join(" ");
+                let _ = writeln!(s, "\tpinctrl-0 = <{pin_nodes}>;");
+                s.push_str("\tpinctrl-names = \"default\";\n");
+            }
+        }
+
+        Ok(Some(s))
+    }
+}
+
+fn render_dts<'a>(
+    target: &'a Target,
+    gpios: &Gpios,
+    peripherals: &ZephyrChipPeripherals,
+    resources: &mut Resources<'a>,
+) -> Result<String> {
+    let mut s = String::new();
+    // maps alias names to node labels
+    let mut aliases = BTreeMap::new();
+
+    s.push_str("/dts-v1/;\n");
+    let _ = writeln!(s, "#include <{}>", gpios.zephyr_chip.dtsi);
+    s.push('\n');
+
+    s.push_str("/ {\n");
+    let _ = writeln!(
+        s,
+        "\tmodel = {};",
+        quote(target.description.as_deref().unwrap_or(&target.name))
+    );
+
+    if let Some((bus, _)) = host_facing_uart(target) {
+        if let Some(label) = node_label(resources, &bus, &peripherals.uarts)
+            && gpios.zephyr_chip.pinctrl.is_some()
+        {
+            s.push_str("\n\tchosen {\n");
+            let _ = writeln!(s, "\t\tzephyr,console = &{label};");
+            let _ = writeln!(s, "\t\tzephyr,shell-uart = &{label};");
+            s.push_str("\t};\n");
+        } else {
+            eprintln!(
+                "warning: {}: {bus} cannot be enabled, the board has no console",
+                target.name
+            );
+        }
+    }
+
+    if target.has_leds() {
+        s.push_str(&render_leds(target, gpios, resources, &mut aliases)?);
+    }
+    if target.has_buttons() {
+        s.push_str(&render_buttons(target, gpios, resources, &mut aliases)?);
+    }
+
+    if !aliases.is_empty() {
+        s.push_str("\n\taliases {\n");
+        for (alias, label) in &aliases {
+            let _ = writeln!(s, "\t\t{alias} = &{label};");
+        }
+        s.push_str("\t};\n");
+    }
+
+    s.push_str("};\n");
+
+    // GPIO controllers
+    let used_ports = crate::chips::target_pins(target)
+        .into_iter()
+        .filter_map(|(pin, _)| gpios.pins.parse(pin))
+        .map(|(port, _)| port)
+        .collect::<BTreeSet<_>>();
+    for port in used_ports {
+        if let Some(controller) = gpios.zephyr_chip.gpio_ports.get(usize::from(port)) {
+            let _ = write!(s, "\n&{controller} {{\n\tstatus = \"okay\";\n}};\n");
+        }
+    }
+
+    let mut pinctrl_nodes = String::new();
+    s.push_str(&render_uarts(
+        target,
+        gpios,
+        peripherals,
+        resources,
+        &mut pinctrl_nodes,
+    )?);
+    s.push_str(&render_i2cs(
+        target,
+        gpios,
+        peripherals,
+        resources,
+        &mut pinctrl_nodes,
+    )?);
+    s.push_str(&render_spis(
+        target,
+        gpios,
+        peripherals,
+        resources,
+        &mut pinctrl_nodes,
+    )?);
+    if !pinctrl_nodes.is_empty() {
+        let _ = write!(s, "\n&pinctrl {{{pinctrl_nodes}}};\n");
+    }
+
+    Ok(s)
+}
+
+/// Returns the devicetree node label of the peripheral assigned to `bus`.
+fn node_label<'a>(
+    resources: &Resources,
+    bus: &str,
+    labels: &'a BTreeMap<String, String>,
+) -> Option<&'a str> {
+    resources
+        .peripheral(bus)
+        .and_then(|peripheral| labels.get(peripheral))
+        .map(String::as_str)
+}
+
+/// Converts a board alias to a devicetree alias name (lowercase, dashes).
+fn dt_alias(alias: &str) -> String {
+    alias.to_lowercase().replace('_', "-")
+}
+
+/// Renders the `leds` node.
+fn render_leds<'a>(
+    target: &'a Target,
+    gpios: &Gpios,
+    resources: &mut Resources<'a>,
+    aliases: &mut BTreeMap<String, String>,
+) -> Result<String> {
+    let mut s = String::new();
+
+    s.push_str("\n\tleds {\n\t\tcompatible = \"gpio-leds\";\n");
+    for (n, led) in target.leds.iter().enumerate() {
+        resources.claim(&led.pin, format!("led{n}"))?;
+
+        let active = if led.active == Some(PinActive::Low) {
+            "GPIO_ACTIVE_LOW"
+        } else {
+            "GPIO_ACTIVE_HIGH"
+        };
+        let label = format!("led{n}");
+
+        let _ = writeln!(s, "\t\t{label}: led_{n} {{");
+        let _ = writeln!(s, "\t\t\tgpios = <{}>;", gpios.spec(&led.pin, &[active])?);
+        match led.color {
+            Some(ref color) => {
+                let _ = writeln!(s, "\t\t\tlabel = \"LED {n} ({color})\";");
+            }
+            None => {
+                let _ = writeln!(s, "\t\t\tlabel = \"LED {n}\";");
+            }
+        }
+        s.push_str("\t\t};\n");
+
+        aliases.insert(label.clone(), label.clone());
+        for alias in &led.aliases {
+            aliases.insert(dt_alias(alias), label.clone());
+        }
+    }
+    s.push_str("\t};\n");
+
+    Ok(s)
+}
+
+/// Renders the `buttons` node.
+fn render_buttons<'a>(
+    target: &'a Target,
+    gpios: &Gpios,
+    resources: &mut Resources<'a>,
+    aliases: &mut BTreeMap<String, String>,
+) -> Result<String> {
+    let mut s = String::new();
+
+    s.push_str("\n\tbuttons {\n\t\tcompatible = \"gpio-keys\";\n");
+    // `gpio-keys` only supports a single debounce interval for all buttons.
+    if let Some(debounce_ms) = target.buttons.iter().filter_map(|b| b.debounce_ms).max() {
+        let _ = writeln!(s, "\t\tdebounce-interval-ms = <{debounce_ms}>;");
+    }
+    for (n, button) in target.buttons.iter().enumerate() {
+        resources.claim(&button.pin, format!("button{n}"))?;
+
+        let mut flags = Vec::new();
+        match button.effective_pull() {
+            Pull::Up => flags.push("GPIO_PULL_UP"),
+            Pull::Down => flags.push("GPIO_PULL_DOWN"),
+            Pull::None => {}
+        }
+        flags.push(if button.active == Some(PinActive::Low) {
+            "GPIO_ACTIVE_LOW"
+        } else {
+            "GPIO_ACTIVE_HIGH"
+        });
+        let label = format!("button{n}");
+
+        let _ = writeln!(s, "\t\t{label}: button_{n} {{");
+        let _ = writeln!(s, "\t\t\tgpios = <{}>;", gpios.spec(&button.pin, &flags)?);
+        let _ = writeln!(s, "\t\t\tlabel = \"Button {n}\";");
+        s.push_str("\t\t};\n");
+
+        aliases.insert(format!("sw{n}"), label.clone());
+        for alias in &button.aliases {
+            aliases.insert(dt_alias(alias), label.clone());
+        }
+    }
+    s.push_str("\t};\n");
+
+    Ok(s)
+}
+
+/// Returns the warning for a bus that is not enabled because its pins cannot be expressed.
+fn no_pinctrl_warning(target: &Target, bus: &str) -> String {
+    format!(
+        "warning: {}: {bus} is not enabled, no pinctrl conventions are known for chip {}",
+        target.name, target.chip
+    )
+}
+
+/// Enables the UART nodes, with their pins.
+fn render_uarts<'a>(
+    target: &'a Target,
+    gpios: &Gpios,
+    peripherals: &ZephyrChipPeripherals,
+    resources: &mut Resources<'a>,
+    pinctrl_nodes: &mut String,
+) -> Result<String> {
+    let mut s = String::new();
+
+    let uarts = target
+        .debugger_uart()
+        .map(|uart| ("debugger_uart".to_string(), uart))
+        .into_iter()
+        .chain(
+            target
+                .uarts
+                .iter()
+                .enumerate()
+                .map(|(n, uart)| (format!("uart{n}"), uart)),
+        );
+
+    for (bus, uart) in uarts {
+        resources.claim(&uart.rx_pin, &bus)?;
+        resources.claim(&uart.tx_pin, &bus)?;
+
+        let Some(label) = node_label(resources, &bus, &peripherals.uarts) else {
+            eprintln!("warning: {}: no peripheral found for {bus}", target.name);
+            continue;
+        };
+
+        let mut signals = vec![("tx", uart.tx_pin.as_str()), ("rx", uart.rx_pin.as_str())];
+        if let (Some(rts_pin), Some(cts_pin)) = (&uart.rts_pin, &uart.cts_pin) {
+            signals.extend([("rts", rts_pin.as_str()), ("cts", cts_pin.as_str())]);
+        }
+        let Some(pinctrl) = gpios.pinctrl(label, PeripheralKind::Uart, &signals, pinctrl_nodes)?
+        else {
+            eprintln!("{}", no_pinctrl_warning(target, &bus));
+            continue;
+        };
+
+        let _ = writeln!(s, "\n&{label} {{");
+        s.push_str("\tstatus = \"okay\";\n");
+        s.push_str(&pinctrl);
+        s.push_str("\tcurrent-speed = <115200>;\n");
+        if uart.cts_pin.is_some() && uart.rts_pin.is_some() {
+            s.push_str("\thw-flow-control;\n");
+        }
+        s.push_str("};\n");
+    }
+
+    Ok(s)
+}
+
+/// Enables the I2C nodes, with their pins.
+fn render_i2cs<'a>(
+    target: &'a Target,
+    gpios: &Gpios,
+    peripherals: &ZephyrChipPeripherals,
+    resources: &mut Resources<'a>,
+    pinctrl_nodes: &mut String,
+) -> Result<String> {
+    let mut s = String::new();
+
+    for (n, i2c) in target.i2cs.iter().enumerate() {
+        let bus = format!("i2c{n}");
+        resources.claim(&i2c.sda_pin, &bus)?;
+        resources.claim(&i2c.scl_pin, &bus)?;
+
+        let Some(label) = node_label(resources, &bus, &peripherals.i2cs) else {
+            eprintln!("warning: {}: no peripheral found for {bus}", target.name);
+            continue;
+        };
+
+        let signals = [("sda", i2c.sda_pin.as_str()), ("scl", i2c.scl_pin.as_str())];
+        let Some(pinctrl) = gpios.pinctrl(label, PeripheralKind::I2c, &signals, pinctrl_nodes)?
+        else {
+            eprintln!("{}", no_pinctrl_warning(target, &bus));
+            continue;
+        };
+
+        let _ = writeln!(s, "\n&{label} {{");
+        s.push_str("\tstatus = \"okay\";\n");
+        s.push_str(&pinctrl);
+        if let Some(frequency) = i2c.frequency {
+            let _ = writeln!(s, "\tclock-frequency = <{frequency}>;");
+        }
+        s.push_str("};\n");
+    }
+
+    Ok(s)
+}
+
+/// Enables the SPI nodes, with their pins and chip-select lines.
+fn render_spis<'a>(
+    target: &'a Target,
+    gpios: &Gpios,
+    peripherals: &ZephyrChipPeripherals,
+    resources: &mut Resources<'a>,
+    pinctrl_nodes: &mut String,
+) -> Result<String> {
+    let mut s = String::new();
+
+    for (n, spi) in target.spis.iter().enumerate() {
+        let bus = format!("spi{n}");
+        resources.claim(&spi.sck_pin, &bus)?;
+        for pin in [&spi.mosi_pin, &spi.miso_pin].into_iter().flatten() {
+            resources.claim(pin, &bus)?;
+        }
+        for (m, cs) in spi.cs.iter().enumerate() {
+            resources.claim(&cs.pin, format!("{bus} (cs{m})"))?;
+        }
+
+        let Some(label) = node_label(resources, &bus, &peripherals.spis) else {
+            eprintln!("warning: {}: no peripheral found for {bus}", target.name);
+            continue;
+        };
+
+        let mut signals = vec![("sck", spi.sck_pin.as_str())];
+        if let Some(ref mosi_pin) = spi.mosi_pin {
+            signals.push(("mosi", mosi_pin));
+        }
+        if let Some(ref miso_pin) = spi.miso_pin {
+            signals.push(("miso", miso_pin));
+        }
+        let Some(pinctrl) = gpios.pinctrl(label, PeripheralKind::Spi, &signals, pinctrl_nodes)?
+        else {
+            eprintln!("{}", no_pinctrl_warning(target, &bus));
+            continue;
+        };
+
+        let _ = writeln!(s, "\n&{label} {{");
+        s.push_str("\tstatus = \"okay\";\n");
+        s.push_str(&pinctrl);
+        if !spi.cs.is_empty() {
+            let cs_gpios = spi
+                .cs
+                .iter()
+                .map(|cs| Ok(format!("<{}>", gpios.spec(&cs.pin, &["GPIO_ACTIVE_LOW"])?)))
+                .collect::<Result<Vec<_>>>()?;
+            let _ = writeln!(s, "\tcs-gpios = {};", cs_gpios.join(", "));
+        }
+        s.push_str("};\n");
+    }
+
+    Ok(s)
+}
+
+#[cfg(test)]
+fn test_zephyr_chip() -> ZephyrChipMapEntry {
+    ZephyrChipMapEntry {
+        soc: "nrf52840".to_string(),
+        soc_kconfig: "SOC_NRF52840_QIAA".to_string(),
+        dtsi: "nordic/nrf52840_qiaa.dtsi".to_string(),
+        gpio_ports: vec!["gpio0".to_string(), "gpio1".to_string()],
+        peripherals: None,
+        pinctrl: Some(ZephyrPinctrl::Nrf),
+    }
+}
+
+#[test]
+fn test_render_leds_and_buttons() {
+    use sbd_gen_schema::{Button, Led};
+
+    let target = Target {
+        leds: vec![Led {
+            pin: "P0_13".to_owned(),
+            color: Some("green".to_owned()),
+            active: Some(PinActive::Low),
+            aliases: vec!["LED_GREEN".to_owned()],
+        }],
+        buttons: vec![
+            Button {
+                pin: "P1_01".to_owned(),
+                active: Some(PinActive::Low),
+                aliases: vec![],
+                pull: None,
+                debounce_ms: Some(20),
+            },
+            Button {
+                pin: "P0_25".to_owned(),
+                active: None,
+                aliases: vec!["USER".to_owned()],
+                pull: Some(Pull::None),
+                debounce_ms: None,
+            },
+        ],
+        ..crate::ariel::test_default_target()
+    };
+
+    let zephyr_chip = test_zephyr_chip();
+    let gpios = Gpios {
+        zephyr_chip: &zephyr_chip,
+        pins: ChipPins::default(),
+    };
+    let mut resources = Resources::new(&target, None, None);
+    let mut aliases = BTreeMap::new();
+
+    let rendered = render_leds(&target, &gpios, &mut resources, &mut aliases).unwrap()
+        + &render_buttons(&target, &gpios, &mut resources, &mut aliases).unwrap();
+    assert_eq!(
+        rendered,
+        "
+\tleds {
+\t\tcompatible = \"gpio-leds\";
+\t\tled0: led_0 {
+\t\t\tgpios = <&gpio0 13 GPIO_ACTIVE_LOW>;
+\t\t\tlabel = \"LED 0 (green)\";
+\t\t};
+\t};
+
+\tbuttons {
+\t\tcompatible = \"gpio-keys\";
+\t\tdebounce-interval-ms = <20>;
+\t\tbutton0: button_0 {
+\t\t\tgpios = <&gpio1 1 (GPIO_PULL_UP | GPIO_ACTIVE_LOW)>;
+\t\t\tlabel = \"Button 0\";
+\t\t};
+\t\tbutton1: button_1 {
+\t\t\tgpios = <&gpio0 25 GPIO_ACTIVE_HIGH>;
+\t\t\tlabel = \"Button 1\";
+\t\t};
+\t};
+"
+    );
+
+    assert_eq!(
+        aliases.into_iter().collect::<Vec<_>>(),
+        [
+            ("led-green".to_string(), "led0".to_string()),
+            ("led0".to_string(), "led0".to_string()),
+            ("sw0".to_string(), "button0".to_string()),
+            ("sw1".to_string(), "button1".to_string()),
+            ("user".to_string(), "button1".to_string()),
+        ]
+    );
+}
+
+/// Renders the UART and I2C nodes of a test target, returning them and the pinctrl nodes.
+#[cfg(test)]
+fn render_test_buses(zephyr_chip: &ZephyrChipMapEntry) -> (String, String) {
+    use sbd_gen_schema::I2c;
+
+    let target = Target {
+        uarts: vec![Uart {
+            aliases: vec![],
+            rx_pin: "P0_08".to_owned(),
+            tx_pin: "P0_06".to_owned(),
+            cts_pin: None,
+            rts_pin: None,
+            possible_peripherals: vec![],
+            host_facing: true,
+        }],
+        i2cs: vec![I2c {
+            aliases: vec![],
+            sda_pin: "P0_26".to_owned(),
+            scl_pin: "P1_02".to_owned(),
+            possible_peripherals: vec![],
+            frequency: Some(400_000),
+            external_pull_ups: true,
+        }],
+        ..crate::ariel::test_default_target()
+    };
+    let peripherals = ZephyrChipPeripherals {
+        uarts: BTreeMap::from([("UARTE0".to_string(), "uart0".to_string())]),
+        i2cs: BTreeMap::from([("TWIM1".to_string(), "i2c1".to_string())]),
+        spis: BTreeMap::new(),
+    };
+
+    let gpios = Gpios {
+        zephyr_chip,
+        pins: ChipPins::default(),
+    };
+    let mut resources = Resources::new(&target, None, Some(&os_peripherals(&peripherals)));
+    let mut nodes = String::new();
+    let rendered = render_uarts(&target, &gpios, &peripherals, &mut resources, &mut nodes).unwrap()
+        + &render_i2cs(&target, &gpios, &peripherals, &mut resources, &mut nodes).unwrap();
+    (rendered, nodes)
+}
+
+#[test]
+fn test_render_buses() {
+    let (rendered, nodes) = render_test_buses(&test_zephyr_chip());
+    assert_eq!(
+        rendered,
+        "
+&uart0 {
+\tstatus = \"okay\";
+\tpinctrl-0 = <&uart0_default>;
+\tpinctrl-1 = <&uart0_sleep>;
+\tpinctrl-names = \"default\", \"sleep\";
+\tcurrent-speed = <115200>;
+};
+
+&i2c1 {
+\tstatus = \"okay\";
+\tpinctrl-0 = <&i2c1_default>;
+\tpinctrl-1 = <&i2c1_sleep>;
+\tpinctrl-names = \"default\", \"sleep\";
+\tclock-frequency = <400000>;
+};
+"
+    );
+    assert_eq!(
+        nodes,
+        "
+\tuart0_default: uart0_default {
+\t\tgroup1 {
+\t\t\tpsels = <NRF_PSEL(UART_TX, 0, 6)>,
+\t\t\t\t<NRF_PSEL(UART_RX, 0, 8)>;
+\t\t};
+\t};
+
+\tuart0_sleep: uart0_sleep {
+\t\tgroup1 {
+\t\t\tpsels = <NRF_PSEL(UART_TX, 0, 6)>,
+\t\t\t\t<NRF_PSEL(UART_RX, 0, 8)>;
+\t\t\tlow-power-enable;
+\t\t};
+\t};
+
+\ti2c1_default: i2c1_default {
+\t\tgroup1 {
+\t\t\tpsels = <NRF_PSEL(TWIM_SDA, 0, 26)>,
+\t\t\t\t<NRF_PSEL(TWIM_SCL, 1, 2)>;
+\t\t};
+\t};
+
+\ti2c1_sleep: i2c1_sleep {
+\t\tgroup1 {
+\t\t\tpsels = <NRF_PSEL(TWIM_SDA, 0, 26)>,
+\t\t\t\t<NRF_PSEL(TWIM_SCL, 1, 2)>;
+\t\t\tlow-power-enable;
+\t\t};
+\t};
+"
+    );
+}
+
+#[test]
+fn test_render_buses_pinctrl_styles() {
+    let stm32 = ZephyrChipMapEntry {
+        pinctrl: Some(ZephyrPinctrl::Stm32),
+        ..test_zephyr_chip()
+    };
+    let (rendered, nodes) = render_test_buses(&stm32);
+    assert!(rendered.contains("\tpinctrl-0 = <&uart0_tx_pa6 &uart0_rx_pa8>;\n"));
+    assert!(rendered.contains("\tpinctrl-0 = <&i2c1_sda_pa26 &i2c1_scl_pb2>;\n"));
+    assert!(nodes.is_empty());
+
+    // STM32 ports are letters.
+    let gpios = Gpios {
+        zephyr_chip: &stm32,
+        pins: ChipPins::default(),
+    };
+    let err = gpios
+        .pinctrl(
+            "uart0",
+            PeripheralKind::Uart,
+            &[("tx", "P30_01")],
+            &mut String::new(),
+        )
+        .unwrap_err();
+    assert_eq!(err.to_string(), "uart0: port 30 has no STM32 name");
+
+    // Buses whose pins cannot be expressed are not enabled.
+    let unknown = ZephyrChipMapEntry {
+        pinctrl: None,
+        ..test_zephyr_chip()
+    };
+    assert_eq!(render_test_buses(&unknown), (String::new(), String::new()));
+}
+
+#[test]
+fn test_quote() {
+    let description = "Board \"X\" with a \\ and\ttabs\nand lines";
+    assert_eq!(
+        quote(description),
+        "\"Board \\\"X\\\" with a \\\\ and\\ttabs\\nand lines\""
+    );
+
+    let target = Target {
+        description: Some(description.to_owned()),
+        ..crate::ariel::test_default_target()
+    };
+    let board_yml: serde_yaml::Value =
+        serde_yaml::from_str(&render_board_yml(&target, &test_zephyr_chip())).unwrap();
+    assert_eq!(board_yml["board"]["full_name"].as_str(), Some(description));
+}
+
+#[test]
+fn test_zephyr_runner() {
+    assert_eq!(zephyr_runner("jlink"), Some("jlink"));
+    assert_eq!(zephyr_runner("cmsis-dap"), Some("pyocd"));
+    assert_eq!(zephyr_runner("unknown"), None);
+}